@@ -15,7 +15,7 @@ fn main() {
     // 检测是否安装了 pnpm (Windows 下使用 cmd)
     let has_pnpm = if cfg!(target_os = "windows") {
         Command::new("cmd")
-            .args(&["/C", "pnpm", "--version"])
+            .args(["/C", "pnpm", "--version"])
             .output()
             .is_ok()
     } else {
@@ -35,7 +35,7 @@ fn main() {
     // 安装依赖
     let install_status = if cfg!(target_os = "windows") {
         Command::new("cmd")
-            .args(&["/C", "pnpm", "install"])
+            .args(["/C", "pnpm", "install"])
             .current_dir(frontend_dir)
             .status()
     } else {
@@ -62,12 +62,12 @@ fn main() {
     // 构建前端
     let build_status = if cfg!(target_os = "windows") {
         Command::new("cmd")
-            .args(&["/C", "pnpm", "run", "build"])
+            .args(["/C", "pnpm", "run", "build"])
             .current_dir(frontend_dir)
             .status()
     } else {
         Command::new("pnpm")
-            .args(&["run", "build"])
+            .args(["run", "build"])
             .current_dir(frontend_dir)
             .status()
     };
@@ -0,0 +1,129 @@
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::cookie::CookieJar;
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::{Duration, SystemTime}};
+use tracing::{error, info, warn};
+
+use crate::{AppState, cookies, csrf, error::ApiError, oauth::{self, SessionInfo, UnifiedUserInfo}, upload};
+
+/// 删除确认令牌的有效期
+const DELETION_CONFIRM_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// 导出当前用户的全部数据
+///
+/// 只包含已认证用户自己的信息（用户信息、上传的文件与配额用量、会话），不涉及任何其他用户
+pub async fn export_user(
+    State(state): State<Arc<AppState>>,
+    user: UnifiedUserInfo,
+    session: SessionInfo,
+) -> Result<impl IntoResponse, ApiError> {
+    info!("用户导出数据: subject={}", user.subject);
+
+    let files = upload::user_files(&state, &upload::storage_owner(&user.subject)).await
+        .map_err(|e| {
+            error!("读取用户文件失败: subject={}, {:?}", user.subject, e);
+            ApiError::internal("Failed to list stored files")
+        })?;
+    let used: u64 = files.iter().map(|file| file.size).sum();
+
+    Ok(Json(serde_json::json!({
+        "linked_providers": [user.provider.clone()],
+        "sessions": [session],
+        "files": files,
+        "quota": {
+            "used": used,
+            "limit": state.config().server.max_user_quota_bytes,
+        },
+        "user": user,
+    })))
+}
+
+/// 删除账号的确认声明，签名后作为确认令牌下发
+#[derive(Serialize, Deserialize)]
+struct DeletionConfirmation {
+    subject: String,
+    issued_at: SystemTime,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteAccountQuery {
+    pub confirm: Option<String>,
+}
+
+/// 删除当前用户的账号及全部数据
+///
+/// 分两步进行：不带 `confirm` 参数调用时返回 428 和一个短期有效的确认令牌，
+/// 携带该令牌再次调用才会真正执行删除：删除用户上传的全部文件，提供者支持时吊销上游令牌，
+/// 并使该用户的所有会话失效。
+///
+/// 删除后当前会话随之失效，用同一个 token 再次调用会在认证阶段返回 401 `session_revoked`；
+/// 重新登录后再次删除时没有可删除的数据，同样返回 204。
+pub async fn delete_account(
+    State(state): State<Arc<AppState>>,
+    user: UnifiedUserInfo,
+    session: SessionInfo,
+    Query(query): Query<DeleteAccountQuery>,
+    headers: HeaderMap,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, ApiError> {
+    if let Some(max_age) = state.config().oauth.step_up_max_age_secs {
+        session.require_fresh_auth(Duration::from_secs(max_age))?;
+    }
+
+    let Some(confirm) = query.confirm else {
+        let confirmation = DeletionConfirmation {
+            subject: user.subject.clone(),
+            issued_at: SystemTime::now(),
+        };
+        let confirm_token = state.signer().sign(&confirmation)
+            .map_err(|_| ApiError::internal("Server failed to sign confirmation token"))?;
+
+        return Err(ApiError::new(StatusCode::PRECONDITION_REQUIRED, "Confirmation required")
+            .with_code("confirmation_required")
+            .with_field("confirm_token", confirm_token)
+            .with_field("expires_in", DELETION_CONFIRM_TTL.as_secs()));
+    };
+
+    let confirmation: DeletionConfirmation = state.signer().verify(&confirm)
+        .map_err(|_| ApiError::bad_request("Invalid confirmation token").with_code("invalid_confirmation"))?;
+
+    let expired = SystemTime::now()
+        .duration_since(confirmation.issued_at)
+        .map(|age| age > DELETION_CONFIRM_TTL)
+        .unwrap_or(true);
+    if confirmation.subject != user.subject || expired {
+        return Err(ApiError::bad_request("Confirmation token expired or not issued for this user")
+            .with_code("invalid_confirmation"));
+    }
+
+    let deleted_files = upload::delete_user_files(&state, &upload::storage_owner(&user.subject)).await
+        .map_err(|e| {
+            error!("删除用户文件失败: subject={}, {:?}", user.subject, e);
+            ApiError::internal("Failed to delete stored files")
+        })?;
+    // 上游令牌吊销失败不影响本地数据的删除
+    if let Err(e) = oauth::revoke_upstream_tokens(&state, &headers).await {
+        warn!("吊销上游令牌失败: subject={}, {}", user.subject, e);
+    }
+
+    // 吊销该用户的所有会话，其它设备上的 token 也随之失效
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let max_lifetime = Duration::from_secs(state.config().oauth.max_session_lifetime_secs);
+    state.sessions.revoke_all(&user.subject, now, max_lifetime);
+    info!(target: "audit", "账号已删除: subject={}, provider={}, files={}", user.subject, user.provider, deleted_files);
+    state.events.record("account_deleted", Some(&user.provider), Some(&user.subject), None);
+
+    let config = state.config();
+    let jar = cookies::remove_session(&config.cookie, jar)
+        .remove(csrf::removal_cookie(&config.cookie));
+
+    Ok((jar, StatusCode::NO_CONTENT))
+}
@@ -0,0 +1,175 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+    Json,
+};
+use serde::Serialize;
+use std::{sync::{Arc, Mutex}, time::{Duration, SystemTime}};
+use tracing::{error, info, warn};
+
+use crate::{AppState, CONFIG_FILE, config::Config, error::ApiError, oauth::{SessionInfo, UnifiedUserInfo, http::OutboundClients}};
+
+/// 管理员鉴权中间件
+///
+/// 必须放在 auth_middleware 之后，从 extensions 中读取用户信息，
+/// 只有 subject（`{provider}:{uid}`）位于 `admin_uids` 中的用户才能继续访问。
+pub async fn admin_middleware(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let user = request
+        .extensions()
+        .get::<UnifiedUserInfo>()
+        .ok_or_else(|| ApiError::unauthorized("Authentication required"))?;
+
+    let config = state.config();
+    if !config.server.admin_uids.contains(&user.subject) {
+        warn!("非管理员用户尝试访问管理接口: subject={}", user.subject);
+        return Err(ApiError::forbidden("Admin privileges required").with_code("admin_required"));
+    }
+
+    // 管理操作要求近期重新认证
+    if let Some(max_age) = config.oauth.step_up_max_age_secs {
+        let session = request
+            .extensions()
+            .get::<SessionInfo>()
+            .ok_or_else(|| ApiError::unauthorized("Authentication required"))?;
+        session.require_fresh_auth(Duration::from_secs(max_age))?;
+    }
+
+    Ok(next.run(request).await)
+}
+
+/// 最近一次失败的配置重新加载
+#[derive(Debug, Clone, Serialize)]
+pub struct ReloadFailure {
+    /// 失败时间（Unix 时间戳，秒）
+    pub failed_at: u64,
+    /// 发起重新加载的管理员
+    pub subject: String,
+    pub errors: Vec<String>,
+}
+
+/// 配置重新加载状态
+///
+/// 重新加载失败后服务继续使用旧配置，此时磁盘上的配置文件与运行中的配置不一致，
+/// 直到下一次重新加载成功为止
+#[derive(Default)]
+pub struct ReloadStatus {
+    last_failure: Mutex<Option<ReloadFailure>>,
+}
+
+impl ReloadStatus {
+    fn record_failure(&self, failure: ReloadFailure) {
+        *self.last_failure.lock().expect("reload status lock poisoned") = Some(failure);
+    }
+
+    fn clear(&self) {
+        *self.last_failure.lock().expect("reload status lock poisoned") = None;
+    }
+
+    /// 上次重新加载失败的信息，之后已成功重新加载时为 None
+    pub fn last_failure(&self) -> Option<ReloadFailure> {
+        self.last_failure.lock().expect("reload status lock poisoned").clone()
+    }
+}
+
+/// 处理重新加载失败：记录 ERROR 日志与状态，按配置发送 webhook 通知，并构造响应
+fn reload_failed(state: &AppState, user: &UnifiedUserInfo, errors: Vec<String>) -> ApiError {
+    error!(target: "audit", "配置重新加载失败，继续使用旧配置: subject={}, errors={:?}", user.subject, errors);
+    state.events.record("config_reload_failed", None, Some(&user.subject), Some(errors.join("; ")));
+
+    let failure = ReloadFailure {
+        failed_at: SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        subject: user.subject.clone(),
+        errors: errors.clone(),
+    };
+    state.reload_status.record_failure(failure.clone());
+
+    // webhook 地址取自当前运行的配置，新配置未通过校验不可信
+    if let Some(url) = state.config().server.reload_failure_webhook.clone() {
+        let client = state.default_http_client();
+        tokio::spawn(async move {
+            let result = client
+                .post(&url)
+                .json(&serde_json::json!({ "event": "config_reload_failed", "failure": failure }))
+                .send().await
+                .and_then(|response| response.error_for_status());
+            if let Err(e) = result {
+                warn!("配置重新加载失败通知发送失败 {}: {}", url, e);
+            }
+        });
+    }
+
+    ApiError::bad_request("Config reload failed")
+        .with_code("invalid_config")
+        .with_field("reloaded", false)
+        .with_field("errors", errors)
+}
+
+/// 查询配置重新加载状态（仅管理员）
+///
+/// `stale` 为 true 表示上次重新加载失败，运行中的配置与磁盘上的文件不一致
+pub async fn reload_status(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
+    let last_failure = state.reload_status.last_failure();
+    Json(serde_json::json!({
+        "stale": last_failure.is_some(),
+        "last_failure": last_failure,
+    }))
+}
+
+/// 重新加载配置文件（仅管理员）
+///
+/// 新配置校验通过后原子替换当前配置；校验失败时返回 400 与错误列表，并保留旧配置。
+/// 监听地址、密钥等启动时读取的配置需要重启才会生效。
+pub async fn reload_config(
+    State(state): State<Arc<AppState>>,
+    user: UnifiedUserInfo,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let new_config = Config::load(CONFIG_FILE)
+        .map_err(|e| reload_failed(&state, &user, vec![format!("{:#}", e)]))?;
+
+    let errors = new_config.validate();
+    if !errors.is_empty() {
+        return Err(reload_failed(&state, &user, errors));
+    }
+
+    let old_config = state.config();
+    let mut warnings = new_config.warnings();
+    if old_config.server.host != new_config.server.host || old_config.server.port != new_config.server.port {
+        warnings.push("server.host/server.port changes require a restart".to_string());
+    }
+    let signing_keys_changed = old_config.oauth.current_kid != new_config.oauth.current_kid
+        || old_config.oauth.signing_keys.len() != new_config.oauth.signing_keys.len()
+        || old_config.oauth.signing_keys.iter().zip(&new_config.oauth.signing_keys)
+            .any(|(old, new)| old.kid != new.kid || old.algorithm != new.algorithm || old.secret != new.secret);
+    if old_config.oauth.secret_string != new_config.oauth.secret_string || signing_keys_changed {
+        warnings.push("oauth.secret_string/signing_keys changes require a restart".to_string());
+    }
+    let callback_paths_changed = new_config.oauth.providers.iter().any(|(name, provider)| {
+        old_config.oauth.providers.get(name).and_then(|old| old.callback_path.as_ref()) != provider.callback_path.as_ref()
+    });
+    if callback_paths_changed {
+        warnings.push("callback_path changes require a restart".to_string());
+    }
+    if old_config.rate_limit.backend != new_config.rate_limit.backend {
+        warnings.push("rate_limit.backend changes require a restart".to_string());
+    }
+
+    // 重建出站客户端，使新的 CA 证书、TLS 与连接池配置生效
+    let http_clients = OutboundClients::new(&new_config)
+        .map_err(|e| reload_failed(&state, &user, vec![format!("{:#}", e)]))?;
+
+    state.swap_http_clients(http_clients);
+    state.swap_config(new_config);
+    state.reload_status.clear();
+    info!(target: "audit", "配置已重新加载: subject={}", user.subject);
+    state.events.record("config_reloaded", None, Some(&user.subject), None);
+
+    Ok(Json(serde_json::json!({ "reloaded": true, "warnings": warnings })))
+}
@@ -1,22 +1,391 @@
 use anyhow::Result;
+use ipnet::IpNet;
 use serde::{Deserialize, Serialize};
+use url::Url;
 
 use std::fs;
-use std::collections::HashMap;
+use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
-use crate::oauth::OAuthProviderType;
+use crate::{oauth::OAuthProviderType, signing::DEFAULT_KID};
 
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub server: ServerConfig,
     pub oauth: OAuthProvidersConfig,
+    #[serde(default)]
+    pub avatar: AvatarConfig,
+    #[serde(default)]
+    pub cookie: CookieConfig,
+    #[serde(default)]
+    pub outbound: OutboundConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub upload: UploadConfig,
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+}
+
+/// 登录、回调与上传的限流配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    /// 限流计数的存储后端，修改后需要重启才会生效
+    #[serde(default)]
+    pub backend: RateLimitBackendConfig,
+    /// 单个 IP 每分钟在每个提供者上最多发起的登录次数，超出时返回 429；未设置时不限制
+    #[serde(default)]
+    pub login_per_minute: Option<u32>,
+    /// 单个 IP 每分钟在每个提供者上最多的 OAuth 回调次数，超出时返回 429；未设置时不限制
+    #[serde(default)]
+    pub callback_per_minute: Option<u32>,
+}
+
+/// 限流计数的存储后端
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum RateLimitBackendConfig {
+    /// 保存在进程内存中，多实例部署时每个实例单独计数
+    #[default]
+    Memory,
+    /// 保存在 Redis 中，所有实例共享计数
+    Redis(RedisRateLimitConfig),
+}
+
+/// Redis 限流后端的配置（需要 Redis 5 及以上）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RedisRateLimitConfig {
+    /// 连接地址，如 `redis://127.0.0.1:6379/0`
+    pub url: String,
+    /// 计数 key 的前缀，多个服务共用同一个 Redis 时用于区分
+    #[serde(default = "default_rate_limit_key_prefix")]
+    pub key_prefix: String,
+}
+
+/// 上传接口的配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadConfig {
+    /// 允许上传的表单字段名，包含其它字段的请求会被拒绝（400）
+    #[serde(default = "default_allowed_field_names")]
+    pub allowed_field_names: Vec<String>,
+    /// 每个用户每分钟最多发起的上传请求数，超出时返回 429；未设置时不限制
+    #[serde(default)]
+    pub max_requests_per_minute: Option<u32>,
+    /// 每个用户每小时最多上传的字节数（与是否去重无关），超出时返回 429；未设置时不限制。
+    /// 与 `max_user_quota_bytes` 不同，这里限制的是上传速度而不是保存的总量
+    #[serde(default)]
+    pub max_bytes_per_hour: Option<u64>,
+}
+
+impl Default for UploadConfig {
+    fn default() -> Self {
+        Self {
+            allowed_field_names: default_allowed_field_names(),
+            max_requests_per_minute: None,
+            max_bytes_per_hour: None,
+        }
+    }
+}
+
+/// 日志文件配置（修改后需要重启才能生效）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    /// 审计日志与访问日志的写入目录，未设置时只输出到终端
+    #[serde(default)]
+    pub directory: Option<PathBuf>,
+    /// 日志文件的滚动周期
+    #[serde(default)]
+    pub rotation: LogRotation,
+    /// 旧日志文件保留的天数，0 表示不删除
+    #[serde(default = "default_log_retain_days")]
+    pub retain_days: usize,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            directory: None,
+            rotation: LogRotation::default(),
+            retain_days: default_log_retain_days(),
+        }
+    }
+}
+
+/// 日志文件的滚动周期
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Hourly,
+    #[default]
+    Daily,
+    /// 不滚动，始终写入同一个文件
+    Never,
+}
+
+/// 访问 OAuth 提供者的出站 HTTP 客户端配置
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutboundConfig {
+    /// 最低 TLS 版本，无法协商到该版本的提供者会连接失败
+    #[serde(default)]
+    pub min_tls_version: TlsVersion,
+    /// TLS 实现
+    #[serde(default)]
+    pub tls_backend: TlsBackend,
+    /// 连接池配置（可被提供者配置覆盖）
+    #[serde(default)]
+    pub pool: PoolConfig,
+    /// 额外信任的 CA 证书文件（PEM，可包含多个证书），用于自签名的提供者；
+    /// 重新加载配置时会重新读取
+    #[serde(default)]
+    pub ca_certs: Vec<PathBuf>,
+    /// 建立连接的超时时间（秒），默认 10 秒
+    #[serde(default)]
+    pub connect_timeout_secs: Option<u64>,
+    /// 读取响应的超时时间（秒），默认 30 秒
+    #[serde(default)]
+    pub read_timeout_secs: Option<u64>,
+}
+
+/// 出站连接池配置，未设置的项使用 reqwest 的默认值
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolConfig {
+    /// 每个主机最多保留的空闲连接数
+    #[serde(default)]
+    pub max_idle_per_host: Option<usize>,
+    /// 空闲连接的超时时间（秒）
+    #[serde(default)]
+    pub idle_timeout_secs: Option<u64>,
+}
+
+impl PoolConfig {
+    /// 用 `fallback` 补全未设置的项
+    pub fn or(&self, fallback: &PoolConfig) -> PoolConfig {
+        PoolConfig {
+            max_idle_per_host: self.max_idle_per_host.or(fallback.max_idle_per_host),
+            idle_timeout_secs: self.idle_timeout_secs.or(fallback.idle_timeout_secs),
+        }
+    }
+}
+
+/// TLS 协议版本
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TlsVersion {
+    #[serde(rename = "1.2")]
+    #[default]
+    Tls12,
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+/// 出站请求使用的 TLS 实现
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TlsBackend {
+    /// 系统原生 TLS
+    #[default]
+    Native,
+    /// rustls
+    Rustls,
+}
+
+/// Cookie 属性配置（会话 cookie 与 CSRF cookie 共用）
+///
+/// `secure` 与 `same_site` 未设置时按 `oauth.prefix_url` 推断：
+/// https 时为 Secure + Lax，否则为不设置 Secure + Strict
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CookieConfig {
+    /// 是否设置 Secure 属性
+    #[serde(default)]
+    pub secure: Option<bool>,
+    /// SameSite 属性
+    #[serde(default)]
+    pub same_site: Option<CookieSameSite>,
+    /// Domain 属性，未设置时 cookie 只发送给当前主机
+    #[serde(default)]
+    pub domain: Option<String>,
+    /// 是否设置 Partitioned 属性（CHIPS），要求 Secure 且 SameSite=None
+    #[serde(default)]
+    pub partitioned: bool,
+    /// 会话 token 超过单个 cookie 大小时最多拆分成多少个 cookie，0 表示不拆分（此时登录会失败）
+    #[serde(default)]
+    pub max_chunks: usize,
+}
+
+impl CookieConfig {
+    /// 是否设置 Secure 属性
+    pub fn secure(&self) -> bool {
+        self.secure.unwrap_or(false)
+    }
+
+    /// SameSite 属性
+    pub fn same_site(&self) -> CookieSameSite {
+        self.same_site.unwrap_or_default()
+    }
+
+    /// 按对外地址补全未设置的属性
+    ///
+    /// 经过 https 的 OAuth 回调是跨站导航，SameSite=Strict 会使浏览器在跳转回首页时不携带刚设置的会话 cookie
+    fn apply_defaults(&mut self, prefix_url: &str) {
+        let https = Url::parse(prefix_url).is_ok_and(|url| url.scheme() == "https");
+        if https {
+            self.secure.get_or_insert(true);
+            self.same_site.get_or_insert(CookieSameSite::Lax);
+        }
+    }
+}
+
+/// Cookie 的 SameSite 属性
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CookieSameSite {
+    #[default]
+    Strict,
+    Lax,
+    None,
+}
+/// 头像兜底配置（提供者未提供头像时使用）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AvatarConfig {
+    /// 是否根据邮箱生成 Gravatar 头像
+    #[serde(default)]
+    pub gravatar: bool,
+    /// 默认头像地址
+    #[serde(default)]
+    pub default_url: Option<String>,
+    /// 根据主角色生成头像的地址模板，支持 `{id}` 与 `{name}` 占位符，优先于 Gravatar
+    #[serde(default)]
+    pub profile_template: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
+    /// 管理员列表（可访问 /api/admin/* 接口），格式为 `{provider}:{uid}`
+    #[serde(default)]
+    pub admin_uids: Vec<String>,
+    /// 是否以维护模式启动（拒绝登录与写操作，已登录用户仍可读取）
+    #[serde(default)]
+    pub maintenance_mode: bool,
+    /// 维护模式下展示给用户的提示信息
+    #[serde(default = "default_maintenance_message")]
+    pub maintenance_message: String,
+    /// 受信任的内网地址段（可访问 internal 提供者）
+    #[serde(default)]
+    pub trusted_cidrs: Vec<IpNet>,
+    /// 是否对写请求启用 CSRF 校验（double-submit cookie）
+    #[serde(default = "default_true")]
+    pub csrf_protection: bool,
+    /// 路径末尾斜杠的处理方式
+    #[serde(default)]
+    pub trailing_slash: TrailingSlash,
+    /// 内存中保留的最近事件数量（供 /api/admin/events 调试），0 表示不记录
+    #[serde(default = "default_event_buffer_size")]
+    pub event_buffer_size: usize,
+    /// 收到关闭信号后等待进行中请求完成的最长时间（秒）
+    #[serde(default = "default_shutdown_grace_secs")]
+    pub shutdown_grace_secs: u64,
+    /// 调用 /api/token/introspect 时需要在 `X-Introspection-Secret` 中提供的共享密钥
+    /// （受信任地址段的调用方无需提供）
+    #[serde(default)]
+    pub introspection_secret: Option<String>,
+    /// 始终以 RFC 9457 `application/problem+json` 返回错误
+    /// （关闭时只对 `Accept` 中请求了该类型的客户端生效）
+    #[serde(default)]
+    pub problem_details: bool,
+    /// 启动时以单行 JSON 日志输出启动摘要（供部署工具校验）
+    #[serde(default)]
+    pub startup_summary_log: bool,
+    /// 启动摘要的写入文件
+    #[serde(default)]
+    pub startup_summary_file: Option<PathBuf>,
+    /// `/.well-known/` 路径对应的磁盘目录（如 ACME HTTP-01 验证文件）
+    #[serde(default)]
+    pub well_known_dir: Option<PathBuf>,
+    /// 上传文件的保存目录，每个用户一个子目录（`storage` 为 `local` 时使用）；
+    /// 上传中的文件暂存在其中的 `.tmp` 目录
+    #[serde(default = "default_upload_dir")]
+    pub upload_dir: PathBuf,
+    /// 上传文件的存储后端，修改后需要重启才能生效
+    #[serde(default)]
+    pub storage: StorageConfig,
+    /// 单个上传请求的请求体大小上限（字节，按解压后计算），修改后需要重启才能生效
+    #[serde(default = "default_max_upload_bytes")]
+    pub max_upload_bytes: usize,
+    /// 允许上传的文件扩展名（如 `png`、`zip`、`ysm`），为空时不限制
+    #[serde(default)]
+    pub allowed_extensions: Vec<String>,
+    /// 每个用户可以占用的存储空间上限（字节），未设置时不限制
+    #[serde(default)]
+    pub max_user_quota_bytes: Option<u64>,
+    /// 允许跨域携带 cookie 访问 API 的来源（如 `http://localhost:5173`），为空时只允许同源访问；
+    /// 修改后需要重启才能生效
+    #[serde(default)]
+    pub cors_allowed_origins: Vec<String>,
+    /// 配置重新加载失败时通知的 webhook 地址（POST JSON）
+    #[serde(default)]
+    pub reload_failure_webhook: Option<String>,
+    /// HTTPS 证书配置，未设置时以普通 HTTP 提供服务
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+}
+
+/// HTTPS 证书配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// PEM 格式的证书链文件
+    pub cert_path: PathBuf,
+    /// PEM 格式的私钥文件
+    pub key_path: PathBuf,
+}
+
+/// 上传文件的存储后端
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum StorageConfig {
+    /// 保存到本地的 `upload_dir`
+    #[default]
+    Local,
+    /// S3 兼容的对象存储（如 MinIO）
+    S3(S3StorageConfig),
+}
+
+/// S3 兼容存储的配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct S3StorageConfig {
+    /// 服务地址，未设置时使用 AWS 的默认地址
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    pub bucket: String,
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    /// 使用路径风格的地址（`{endpoint}/{bucket}/{key}`），MinIO 等通常需要开启
+    #[serde(default = "default_true")]
+    pub force_path_style: bool,
+    /// 上传后返回预签名下载地址的有效期（秒），未设置时返回本服务的下载地址
+    #[serde(default)]
+    pub presign_ttl_secs: Option<u64>,
+    /// 上传内容默认使用的存储类别（如 `STANDARD_IA`），未设置时使用 bucket 的默认值
+    #[serde(default)]
+    pub storage_class: Option<String>,
+    /// 允许客户端通过 `X-Storage-Class` 请求头选择的存储类别，为空时不允许选择
+    #[serde(default)]
+    pub allowed_storage_classes: Vec<String>,
+}
+
+/// 路径末尾斜杠的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrailingSlash {
+    /// 透明去除末尾斜杠后再路由
+    #[default]
+    Strip,
+    /// 308 重定向到不带末尾斜杠的地址
+    Redirect,
+    /// 不做处理，严格区分
+    Strict,
 }
 
 /// OAuth 提供者配置集合
@@ -26,10 +395,130 @@ pub struct OAuthProvidersConfig {
     pub prefix_url: String,
     /// 密钥字符串（用于签名 token）
     pub secret_string: String,
+    /// 额外的签名密钥，用于密钥与算法轮换；为空时使用 `secret_string`（HS256）签名。
+    /// `secret_string` 派生的 `default` 密钥始终可以用于验证，除非这里定义了同名密钥
+    #[serde(default)]
+    pub signing_keys: Vec<SigningKeyConfig>,
+    /// 签名新 token 使用的密钥 kid，配置了 `signing_keys` 时必须设置
+    #[serde(default)]
+    pub current_kid: Option<String>,
+    /// 会话过期时间的最大随机抖动（秒），用于分散大量用户同时重新登录
+    #[serde(default)]
+    pub expiry_jitter_secs: Option<u64>,
+    /// 进行中登录流程的数量限制
+    #[serde(default)]
+    pub pending_flows: PendingFlowsConfig,
+    /// 启动时是否检查各启用提供者的主机能否连通
+    #[serde(default)]
+    pub verify_providers: bool,
+    /// 启动连通性检查的超时时间（秒）
+    #[serde(default = "default_verify_timeout_secs")]
+    pub verify_timeout_secs: u64,
+    /// 访问提供者的单个请求的超时时间（秒）
+    #[serde(default = "default_request_timeout_secs")]
+    pub request_timeout_secs: u64,
+    /// OAuth2 state 的有效期（秒）
+    #[serde(default = "default_state_ttl_secs")]
+    pub state_ttl_secs: u64,
+    /// 提供者列表是否按健康状态排序（健康的在前，已熔断的在后）
+    #[serde(default)]
+    pub sort_by_health: bool,
+    /// 会话过期后仍允许用刷新令牌续期的宽限期（秒），0 表示不续期
+    #[serde(default)]
+    pub refresh_grace_period_secs: u64,
+    /// 会话剩余有效期不足该值（秒）时提前用刷新令牌续期，0 表示只在过期后续期
+    ///
+    /// 提前续期失败时原会话继续有效；只对通过 cookie 携带的会话生效
+    #[serde(default)]
+    pub refresh_ahead_secs: u64,
+    /// 从登录起会话的最长存续时间（秒），续期不会延长该时间，超过后需要重新登录
+    #[serde(default = "default_max_session_lifetime_secs")]
+    pub max_session_lifetime_secs: u64,
+    /// 每个用户只允许一个有效会话，新的登录会使之前的会话失效
+    #[serde(default)]
+    pub single_session_per_user: bool,
+    /// 认证失败响应的最短耗时（毫秒），用于消除时间侧信道；不设置时立即返回
+    #[serde(default)]
+    pub auth_failure_delay_ms: Option<u64>,
+    /// 提供者名称的最大长度（超过时直接返回 400）
+    #[serde(default = "default_max_provider_name_len")]
+    pub max_provider_name_len: usize,
+    /// 敏感操作（删除账号、管理接口）要求最近一次认证不早于多少秒之前，不设置时不检查
+    #[serde(default)]
+    pub step_up_max_age_secs: Option<u64>,
+    /// 回调成功后的行为（可被提供者配置覆盖）
+    #[serde(default)]
+    pub callback_success_mode: CallbackSuccessMode,
+    /// `redirect` 模式下保留到跳转地址上的回调查询参数，其余参数（包括 code、state）一律丢弃
+    #[serde(default)]
+    pub preserve_redirect_params: Vec<String>,
+    /// `page` 模式下展示的 HTML（不设置时使用内置页面）
+    #[serde(default)]
+    pub success_page_html: Option<String>,
+    /// 允许的提供者主机名，设置后只能向列表中的主机发起授权（防止配置被篡改后跳转到钓鱼站点）
+    #[serde(default)]
+    pub allowed_provider_hosts: Option<Vec<String>>,
     /// 各个提供者的配置
     pub providers: HashMap<String, OAuthProviderConfig>,
 }
 
+impl OAuthProvidersConfig {
+    /// 判断提供者的 base URL 是否指向允许的主机（未设置允许列表时总是允许）
+    pub fn is_provider_host_allowed(&self, provider_type: &OAuthProviderType) -> bool {
+        let Some(allowed) = &self.allowed_provider_hosts else {
+            return true;
+        };
+        provider_type
+            .endpoint("")
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_lowercase))
+            .is_some_and(|host| allowed.iter().any(|h| h.eq_ignore_ascii_case(&host)))
+    }
+}
+
+/// 签名密钥配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SigningKeyConfig {
+    /// 写入 JWT 头的密钥 ID
+    pub kid: String,
+    /// 签名算法
+    #[serde(default)]
+    pub algorithm: SigningAlgorithmKind,
+    /// 密钥
+    pub secret: String,
+}
+
+/// 签名算法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum SigningAlgorithmKind {
+    #[default]
+    Hs256,
+    Hs384,
+    Hs512,
+}
+
+/// 进行中登录流程的数量限制
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingFlowsConfig {
+    /// 全局最大并发流程数，超过时淘汰最早的流程
+    pub max_total: usize,
+    /// 单个 IP 在每个提供者上的最大并发流程数，超过时返回 429
+    pub max_per_ip: usize,
+    /// 流程有效期（秒）
+    pub ttl_secs: u64,
+}
+
+impl Default for PendingFlowsConfig {
+    fn default() -> Self {
+        Self {
+            max_total: 10000,
+            max_per_ip: 20,
+            ttl_secs: 600,
+        }
+    }
+}
+
 /// 单个 OAuth 提供者配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OAuthProviderConfig {
@@ -39,25 +528,387 @@ pub struct OAuthProviderConfig {
     pub client_id: String,
     /// 客户端密钥
     pub client_secret: String,
-    /// 申请权限
+    /// 申请权限，未设置时使用提供者类型的默认权限
+    #[serde(default)]
     pub scopes: Vec<String>,
+    /// 登录时可以通过 `?scopes=` 申请的其它权限（`scopes` 中的权限总是允许）
+    #[serde(default)]
+    pub allowed_scopes: Vec<String>,
     /// 是否启用
     #[serde(default = "default_true")]
     pub enabled: bool,
+    /// 展示在登录页面上的提供者说明
+    #[serde(default)]
+    pub description: String,
+    /// 可见性（internal 提供者只对受信任地址段开放）
+    #[serde(default)]
+    pub visibility: ProviderVisibility,
+    /// Yggdrasil API 不可用（404）时，是否退回到仅使用角色名
+    #[serde(default = "default_true")]
+    pub yggdrasil_fallback: bool,
+    /// 用户没有任何角色时拒绝登录
+    #[serde(default)]
+    pub require_profile: bool,
+    /// 提供者未授予全部申请的权限时拒绝登录（否则只记录实际授予的权限）
+    #[serde(default)]
+    pub require_all_scopes: bool,
+    /// 因没有角色而拒绝登录时的提示信息
+    #[serde(default = "default_no_profile_message")]
+    pub no_profile_message: String,
+    /// 注入到每个角色 properties 中的额外属性（同名属性会被覆盖）
+    #[serde(default)]
+    pub extra_profile_properties: HashMap<String, String>,
+    /// 覆盖全局的回调成功行为
+    #[serde(default)]
+    pub callback_success_mode: Option<CallbackSuccessMode>,
+    /// 上游响应体的大小上限（字节）
+    #[serde(default = "default_max_response_bytes")]
+    pub max_response_bytes: usize,
+    /// 覆盖全局的出站连接池配置
+    #[serde(default)]
+    pub pool: Option<PoolConfig>,
+    /// 连接失败时重试交换授权码的次数（只重试请求尚未发出的连接错误）
+    #[serde(default)]
+    pub token_exchange_retries: u32,
+    /// 重试的初始退避时间（毫秒），每次重试翻倍
+    #[serde(default = "default_token_exchange_backoff_ms")]
+    pub token_exchange_backoff_ms: u64,
+    /// Microsoft 租户（`common`、`organizations`、`consumers` 或租户 ID），默认为 `common`
+    #[serde(default)]
+    pub tenant: Option<String>,
+    /// `/api/ready` 是否要求该提供者可达
+    #[serde(default = "default_true")]
+    pub required_for_readiness: bool,
+    /// 主角色的选择规则，用于头像与只需要单个角色的场景
+    #[serde(default)]
+    pub primary_profile: PrimaryProfileRule,
+    /// 登出时是否同时跳转到提供者登出
+    #[serde(default)]
+    pub single_logout: bool,
+    /// 提供者的登出地址，不设置时使用提供者内置的地址（如有）
+    #[serde(default)]
+    pub end_session_url: Option<String>,
+    /// 单个 IP 在该提供者上的最大并发登录流程数，覆盖 `pending_flows.max_per_ip`
+    #[serde(default)]
+    pub max_pending_per_ip: Option<usize>,
+    /// 单个 IP 每分钟在该提供者上最多发起的登录次数，覆盖 `rate_limit.login_per_minute`
+    #[serde(default)]
+    pub login_per_minute: Option<u32>,
+    /// 单个 IP 每分钟在该提供者上最多的回调次数，覆盖 `rate_limit.callback_per_minute`
+    #[serde(default)]
+    pub callback_per_minute: Option<u32>,
+    /// 是否使用 PKCE，未设置时 Microsoft 默认开启、Blessing Skin 默认关闭
+    #[serde(default)]
+    pub pkce: Option<bool>,
+    /// 自定义回调路径（如 `/oauth/littleskin/callback`），用于沿用已注册的回调地址
+    #[serde(default)]
+    pub callback_path: Option<String>,
+}
+
+impl OAuthProviderConfig {
+    /// 判断登录时是否允许申请该权限
+    pub fn is_scope_allowed(&self, scope: &str) -> bool {
+        self.scopes.iter().chain(&self.allowed_scopes).any(|s| s == scope)
+    }
+}
+
+/// 回调成功后的行为
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CallbackSuccessMode {
+    /// 重定向到首页
+    #[default]
+    Redirect,
+    /// 展示“可以关闭此窗口”的页面（适用于使用本地回调的桌面应用）
+    Page,
+    /// 设置 cookie 的同时以 JSON 返回会话 token（适用于同时包含网页与原生部分的客户端）
+    Json,
+}
+
+/// 主角色的选择规则
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrimaryProfileRule {
+    /// 提供者返回的第一个角色
+    #[default]
+    First,
+    /// 最近修改的角色（提供者不提供修改时间时退回到第一个）
+    MostRecent,
+    /// 指定名称的角色（不存在时退回到第一个）
+    Name(String),
+}
+
+/// 提供者可见性
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderVisibility {
+    /// 公开提供者，所有人可见
+    #[default]
+    Public,
+    /// 内部提供者，仅受信任地址段可见可用
+    Internal,
+}
+
+/// 提供者名称只能包含字母、数字、`-` 与 `_`，且不超过 `max_len`
+pub fn is_valid_provider_name(name: &str, max_len: usize) -> bool {
+    !name.is_empty()
+        && name.len() <= max_len
+        && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// 内置路由使用的路径前缀，自定义回调路径不能落在其中
+///
+/// 在 `main.rs` 中新增内置路由时需要同步更新，测试会检查两者是否一致
+const RESERVED_PATH_PREFIXES: &[&str] = &[
+    "/api/oauth/",
+    "/api/user",
+    "/api/admin/",
+    "/api/token/",
+    "/api/logout",
+    "/api/maintenance",
+    "/api/session",
+    "/api/upload",
+    "/api/files",
+    "/api/health",
+    "/api/ready",
+    "/readyz",
+];
+
+/// 检查自定义回调路径是否可以注册为路由
+fn validate_callback_path(path: &str) -> Result<(), &'static str> {
+    if !path.starts_with('/') || path == "/" {
+        return Err("must start with / and must not be the root path");
+    }
+    if path.ends_with('/') {
+        return Err("must not end with /");
+    }
+    if path.contains(['{', '}', '*', '?', '#']) {
+        return Err("must be a plain path without parameters or wildcards");
+    }
+    if RESERVED_PATH_PREFIXES.iter().any(|prefix| path.starts_with(prefix)) {
+        return Err("collides with a built-in route");
+    }
+    Ok(())
 }
 
 fn default_true() -> bool {
     true
 }
 
+fn default_upload_dir() -> PathBuf {
+    PathBuf::from("uploads")
+}
+
+fn default_max_upload_bytes() -> usize {
+    10 * 1024 * 1024
+}
+
+fn default_log_retain_days() -> usize {
+    14
+}
+
+fn default_rate_limit_key_prefix() -> String {
+    "ysm_upload:rate_limit:".to_string()
+}
+
+fn default_allowed_field_names() -> Vec<String> {
+    ["skin", "cape", "file"].map(String::from).to_vec()
+}
+
+fn default_shutdown_grace_secs() -> u64 {
+    30
+}
+
+fn default_verify_timeout_secs() -> u64 {
+    5
+}
+
+fn default_request_timeout_secs() -> u64 {
+    15
+}
+
+fn default_state_ttl_secs() -> u64 {
+    10 * 60
+}
+
+fn default_max_session_lifetime_secs() -> u64 {
+    30 * 24 * 60 * 60
+}
+
+fn default_no_profile_message() -> String {
+    "No Minecraft profile found".to_string()
+}
+
+fn default_max_provider_name_len() -> usize {
+    64
+}
+
+fn default_token_exchange_backoff_ms() -> u64 {
+    200
+}
+
+fn default_max_response_bytes() -> usize {
+    10 * 1024 * 1024
+}
+
+fn default_event_buffer_size() -> usize {
+    256
+}
+
+fn default_maintenance_message() -> String {
+    "服务正在维护中，请稍后再试".to_string()
+}
+
 impl Config {
     /// 加载配置文件
     pub fn load(path: &str) -> Result<Self> {
         let content = fs::read_to_string(path)?;
-        let config: Config = serde_yaml::from_str(&content)?;
+        let mut config: Config = serde_yaml::from_str(&content)?;
+        for provider in config.oauth.providers.values_mut() {
+            if provider.scopes.is_empty() {
+                provider.scopes = provider.provider_type.default_scopes();
+            }
+        }
+        config.cookie.apply_defaults(&config.oauth.prefix_url);
         Ok(config)
     }
 
+    /// 校验配置，返回所有发现的错误
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        match Url::parse(&self.oauth.prefix_url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+            Ok(_) => errors.push(format!("oauth.prefix_url must be http(s): {}", self.oauth.prefix_url)),
+            Err(e) => errors.push(format!("oauth.prefix_url is not a valid URL: {}", e)),
+        }
+
+        if self.oauth.secret_string.is_empty() {
+            errors.push("oauth.secret_string must not be empty".to_string());
+        }
+
+        if let StorageConfig::S3(s3) = &self.server.storage {
+            if s3.bucket.is_empty() {
+                errors.push("server.storage.bucket must not be empty".to_string());
+            }
+            if let Some(endpoint) = &s3.endpoint
+                && let Err(e) = Url::parse(endpoint)
+            {
+                errors.push(format!("server.storage.endpoint is not a valid URL: {}", e));
+            }
+        }
+
+        if let RateLimitBackendConfig::Redis(redis) = &self.rate_limit.backend {
+            match Url::parse(&redis.url) {
+                Ok(url) if matches!(url.scheme(), "redis" | "redis+unix" | "unix") => {}
+                Ok(url) => errors.push(format!("rate_limit.backend.url: unsupported scheme {}", url.scheme())),
+                Err(e) => errors.push(format!("rate_limit.backend.url is not a valid URL: {}", e)),
+            }
+        }
+
+        // native-tls 不支持把最低版本设为 TLS 1.3，构建客户端时才会失败
+        if self.outbound.tls_backend == TlsBackend::Native && self.outbound.min_tls_version == TlsVersion::Tls13 {
+            errors.push("outbound.min_tls_version 1.3 requires outbound.tls_backend: rustls".to_string());
+        }
+
+        for origin in &self.server.cors_allowed_origins {
+            // 浏览器发送的 Origin 不带路径与末尾的 `/`，必须按原样比较
+            match Url::parse(origin) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") && url.origin().ascii_serialization() == *origin => {}
+                _ => errors.push(format!("server.cors_allowed_origins: {} must be a bare http(s) origin such as https://example.com", origin)),
+            }
+        }
+
+        let mut kids = HashSet::new();
+        for key in &self.oauth.signing_keys {
+            if key.secret.is_empty() {
+                errors.push(format!("signing key {}: secret must not be empty", key.kid));
+            }
+            if !kids.insert(key.kid.as_str()) {
+                errors.push(format!("signing key {}: duplicate kid", key.kid));
+            }
+        }
+        match &self.oauth.current_kid {
+            Some(kid) if kid != DEFAULT_KID && !kids.contains(kid.as_str()) => {
+                errors.push(format!("oauth.current_kid {} does not match any signing key", kid));
+            }
+            None if !self.oauth.signing_keys.is_empty() => {
+                errors.push("oauth.current_kid must be set when oauth.signing_keys is not empty".to_string());
+            }
+            _ => {}
+        }
+
+        if self.cookie.partitioned && !(self.cookie.secure() && self.cookie.same_site() == CookieSameSite::None) {
+            errors.push("cookie.partitioned requires cookie.secure = true and cookie.same_site = none".to_string());
+        }
+        if self.cookie.same_site() == CookieSameSite::None && !self.cookie.secure() {
+            errors.push("cookie.same_site = none requires cookie.secure = true".to_string());
+        }
+        if let Some(domain) = &self.cookie.domain
+            && (domain.is_empty() || domain.contains(|c: char| c.is_whitespace() || c.is_control() || c == ';' || c == ','))
+        {
+            errors.push(format!("cookie.domain is not a valid domain: {:?}", domain));
+        }
+
+        let mut callback_paths = HashMap::new();
+        for (name, provider) in &self.oauth.providers {
+            if !is_valid_provider_name(name, self.oauth.max_provider_name_len) {
+                errors.push(format!(
+                    "provider {}: name must be at most {} characters of [A-Za-z0-9_-]",
+                    name, self.oauth.max_provider_name_len,
+                ));
+            }
+            if let Some(path) = &provider.callback_path {
+                if let Err(e) = validate_callback_path(path) {
+                    errors.push(format!("provider {}: invalid callback_path {}: {}", name, path, e));
+                } else if let Some(other) = callback_paths.insert(path.as_str(), name) {
+                    errors.push(format!("provider {}: callback_path {} is already used by provider {}", name, path, other));
+                }
+            }
+            if let Some(url) = &provider.end_session_url
+                && let Err(e) = url::Url::parse(url)
+            {
+                errors.push(format!("provider {}: invalid end_session_url {}: {}", name, url, e));
+            }
+            if provider.client_id.is_empty() {
+                errors.push(format!("provider {}: client_id must not be empty", name));
+            }
+            if let Err(e) = provider.provider_type.endpoint("") {
+                errors.push(format!("provider {}: invalid base URL {}: {}", name, provider.provider_type.base_url(), e));
+            } else if !self.oauth.is_provider_host_allowed(&provider.provider_type) {
+                errors.push(format!("provider {}: host of {} is not in oauth.allowed_provider_hosts", name, provider.provider_type.base_url()));
+            }
+        }
+
+        errors
+    }
+
+    /// 检查可能有误但不影响运行的配置，返回所有警告
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        // 多个启用的提供者指向同一上游（同一站点、同一租户）时提醒运维确认
+        let mut by_upstream: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+        for (name, provider) in self.oauth.providers.iter().filter(|(_, provider)| provider.enabled) {
+            let Ok(base) = provider.provider_type.endpoint("") else {
+                continue;
+            };
+            let upstream = match &provider.tenant {
+                Some(tenant) => format!("{} (tenant {})", base, tenant),
+                None => base.to_string(),
+            };
+            by_upstream.entry(upstream).or_default().push(name);
+        }
+        for (upstream, mut names) in by_upstream {
+            if names.len() > 1 {
+                names.sort_unstable();
+                warnings.push(format!("providers {} share the same upstream {}", names.join(", "), upstream));
+            }
+        }
+
+        warnings
+    }
+
     /// 创建默认配置文件
     pub fn create_default(path: &str) -> Result<()> {
         let mut providers = HashMap::new();
@@ -67,8 +918,31 @@ impl Config {
             provider_type: OAuthProviderType::BlessingSkin("https://littleskin.cn".to_string()),
             client_id: "your_client_id_here".to_string(),
             client_secret: "your_client_secret_here".to_string(),
-            scopes: vec!["User.Read".to_string(), "Players.Read".to_string()],
+            scopes: vec!["User.Read".to_string(), "Player.Read".to_string()],
+            allowed_scopes: vec![],
             enabled: true,
+            description: "使用 LittleSkin 账号登录".to_string(),
+            visibility: ProviderVisibility::Public,
+            yggdrasil_fallback: true,
+            require_profile: false,
+            require_all_scopes: false,
+            no_profile_message: default_no_profile_message(),
+            extra_profile_properties: HashMap::new(),
+            callback_success_mode: None,
+            max_response_bytes: default_max_response_bytes(),
+            pool: None,
+            callback_path: None,
+            tenant: None,
+            pkce: None,
+            max_pending_per_ip: None,
+            login_per_minute: None,
+            callback_per_minute: None,
+            required_for_readiness: true,
+            primary_profile: PrimaryProfileRule::First,
+            single_logout: false,
+            end_session_url: None,
+            token_exchange_retries: 0,
+            token_exchange_backoff_ms: default_token_exchange_backoff_ms(),
         });
 
         // Microsoft 提供者示例配置
@@ -76,20 +950,90 @@ impl Config {
             provider_type: OAuthProviderType::Microsoft,
             client_id: "your_azure_client_id".to_string(),
             client_secret: "your_azure_client_secret".to_string(),
-            scopes: vec!["User.Read".to_string()],
+            scopes: vec!["XboxLive.signin".to_string(), "offline_access".to_string()],
+            allowed_scopes: vec![],
             enabled: false, // 默认禁用
+            description: String::new(),
+            visibility: ProviderVisibility::Public,
+            yggdrasil_fallback: true,
+            require_profile: false,
+            require_all_scopes: false,
+            no_profile_message: default_no_profile_message(),
+            extra_profile_properties: HashMap::new(),
+            callback_success_mode: None,
+            max_response_bytes: default_max_response_bytes(),
+            pool: None,
+            callback_path: None,
+            tenant: None,
+            pkce: None,
+            max_pending_per_ip: None,
+            login_per_minute: None,
+            callback_per_minute: None,
+            required_for_readiness: true,
+            primary_profile: PrimaryProfileRule::First,
+            single_logout: false,
+            end_session_url: None,
+            token_exchange_retries: 0,
+            token_exchange_backoff_ms: default_token_exchange_backoff_ms(),
         });
 
         let default_config = Config {
             server: ServerConfig {
                 host: "127.0.0.1".to_string(),
                 port: 3000,
+                admin_uids: Vec::new(),
+                maintenance_mode: false,
+                maintenance_message: default_maintenance_message(),
+                trusted_cidrs: Vec::new(),
+                csrf_protection: true,
+                trailing_slash: TrailingSlash::Strip,
+                event_buffer_size: default_event_buffer_size(),
+                shutdown_grace_secs: default_shutdown_grace_secs(),
+                introspection_secret: None,
+                problem_details: false,
+                startup_summary_log: false,
+                startup_summary_file: None,
+                well_known_dir: None,
+                upload_dir: default_upload_dir(),
+                storage: StorageConfig::Local,
+                max_upload_bytes: default_max_upload_bytes(),
+                allowed_extensions: Vec::new(),
+                max_user_quota_bytes: None,
+                cors_allowed_origins: Vec::new(),
+                tls: None,
+                reload_failure_webhook: None,
             },
             oauth: OAuthProvidersConfig {
                 prefix_url: "http://127.0.0.1:3000".to_string(),
                 secret_string: "your-secret-here-change-this-in-production".to_string(),
+                signing_keys: Vec::new(),
+                current_kid: None,
+                expiry_jitter_secs: None,
+                pending_flows: PendingFlowsConfig::default(),
+                verify_providers: false,
+                verify_timeout_secs: default_verify_timeout_secs(),
+                request_timeout_secs: default_request_timeout_secs(),
+                state_ttl_secs: default_state_ttl_secs(),
+                sort_by_health: false,
+                refresh_grace_period_secs: 0,
+                refresh_ahead_secs: 0,
+                max_session_lifetime_secs: default_max_session_lifetime_secs(),
+                single_session_per_user: false,
+                step_up_max_age_secs: None,
+                max_provider_name_len: default_max_provider_name_len(),
+                auth_failure_delay_ms: None,
+                callback_success_mode: CallbackSuccessMode::Redirect,
+                preserve_redirect_params: Vec::new(),
+                success_page_html: None,
+                allowed_provider_hosts: None,
                 providers,
             },
+            avatar: AvatarConfig::default(),
+            cookie: CookieConfig::default(),
+            outbound: OutboundConfig::default(),
+            logging: LoggingConfig::default(),
+            upload: UploadConfig::default(),
+            rate_limit: RateLimitConfig::default(),
         };
 
         let yaml = serde_yaml::to_string(&default_config)?;
@@ -98,3 +1042,119 @@ impl Config {
     }
 
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 只包含必填项的配置，`providers` 为缩进 4 格的提供者列表
+    fn config_with_providers(providers: &str) -> Config {
+        let yaml = format!(
+            "server:\n  host: 127.0.0.1\n  port: 3000\noauth:\n  prefix_url: http://127.0.0.1:3000\n  secret_string: test-secret\n  providers:\n{}",
+            providers,
+        );
+        serde_yaml::from_str(&yaml).expect("test config should parse")
+    }
+
+    fn provider_with_callback_path(path: &str) -> Config {
+        config_with_providers(&format!(
+            "    littleskin:\n      provider_type: blessingskin=https://littleskin.cn\n      client_id: id\n      client_secret: secret\n      callback_path: {}\n",
+            path,
+        ))
+    }
+
+    #[test]
+    fn callback_path_outside_builtin_routes_is_valid() {
+        assert!(provider_with_callback_path("/oauth/littleskin/callback").validate().is_empty());
+    }
+
+    #[test]
+    fn callback_path_colliding_with_builtin_route_is_rejected() {
+        for path in [
+            "/api/health",
+            "/api/ready",
+            "/readyz",
+            "/api/session",
+            "/api/files/callback",
+            "/api/upload",
+            "/api/oauth/littleskin/callback",
+        ] {
+            let errors = provider_with_callback_path(path).validate();
+            assert!(
+                errors.iter().any(|error| error.contains("collides with a built-in route")),
+                "{} should be rejected, got {:?}", path, errors,
+            );
+        }
+    }
+
+    /// `main.rs` 中注册的内置路由（跳过注释中的示例）
+    fn builtin_routes() -> Vec<&'static str> {
+        include_str!("main.rs")
+            .lines()
+            .filter(|line| !line.trim_start().starts_with("//"))
+            .filter_map(|line| line.split(".route(\"").nth(1)?.split('"').next())
+            .collect()
+    }
+
+    #[test]
+    fn reserved_prefixes_match_builtin_routes() {
+        let routes = builtin_routes();
+        assert!(!routes.is_empty());
+        for route in &routes {
+            assert!(
+                RESERVED_PATH_PREFIXES.iter().any(|prefix| route.starts_with(prefix)),
+                "built-in route {} is not covered by RESERVED_PATH_PREFIXES", route,
+            );
+        }
+        for prefix in RESERVED_PATH_PREFIXES {
+            assert!(
+                routes.iter().any(|route| route.starts_with(prefix)),
+                "RESERVED_PATH_PREFIXES entry {} matches no built-in route", prefix,
+            );
+        }
+    }
+
+    #[test]
+    fn native_tls_with_tls13_minimum_is_rejected() {
+        let mut config = provider_with_callback_path("/oauth/littleskin/callback");
+        config.outbound.min_tls_version = TlsVersion::Tls13;
+        assert!(config.validate().iter().any(|error| error.contains("outbound.min_tls_version")));
+        config.outbound.tls_backend = TlsBackend::Rustls;
+        assert!(config.validate().is_empty());
+    }
+
+    /// 只包含必填项的 Blessing Skin 提供者
+    fn blessing_skin(name: &str, base_url: &str) -> String {
+        format!(
+            "    {}:\n      provider_type: blessingskin={}\n      client_id: {}\n      client_secret: secret\n",
+            name, base_url, name,
+        )
+    }
+
+    fn shared_upstream_config() -> Config {
+        config_with_providers(&(blessing_skin("littleskin", "https://littleskin.cn")
+            + &blessing_skin("littleskin-mirror", "https://littleskin.cn/")))
+    }
+
+    #[test]
+    fn providers_sharing_base_url_are_warned() {
+        assert_eq!(
+            shared_upstream_config().warnings(),
+            ["providers littleskin, littleskin-mirror share the same upstream https://littleskin.cn/"],
+        );
+    }
+
+    #[test]
+    fn disabled_provider_sharing_base_url_is_not_warned() {
+        let mut config = shared_upstream_config();
+        config.oauth.providers.get_mut("littleskin-mirror").unwrap().enabled = false;
+        assert!(config.warnings().is_empty());
+    }
+
+    #[test]
+    fn providers_with_distinct_base_urls_are_not_warned() {
+        let config = config_with_providers(&(blessing_skin("littleskin", "https://littleskin.cn")
+            + &blessing_skin("skin-path", "https://littleskin.cn/skin")));
+        assert!(config.warnings().is_empty());
+    }
+}
@@ -0,0 +1,165 @@
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+
+use crate::config::{CookieConfig, CookieSameSite};
+
+/// 会话 cookie 名称
+pub const SESSION_COOKIE: &str = "access_token";
+
+/// 绑定 OAuth2 state 与浏览器的 cookie 名称
+pub const OAUTH_STATE_COOKIE: &str = "oauth_state";
+
+/// 保存 PKCE code_verifier 的 cookie 名称
+pub const PKCE_VERIFIER_COOKIE: &str = "oauth_pkce";
+
+/// 单个 cookie 分块的最大长度，为名称与属性预留空间（浏览器限制约 4KB）
+const CHUNK_SIZE: usize = 3800;
+
+/// 按配置设置 cookie 的公共属性（Path、Domain、Secure、SameSite、Partitioned）
+///
+/// 设置与删除 cookie 都需要经过这里，否则浏览器可能无法匹配到要删除的 cookie
+pub fn apply_attributes(config: &CookieConfig, cookie: &mut Cookie<'static>) {
+    cookie.set_path("/");
+    if let Some(domain) = &config.domain {
+        cookie.set_domain(domain.clone());
+    }
+    cookie.set_secure(config.secure());
+    cookie.set_same_site(match config.same_site() {
+        CookieSameSite::Strict => SameSite::Strict,
+        CookieSameSite::Lax => SameSite::Lax,
+        CookieSameSite::None => SameSite::None,
+    });
+    if config.partitioned {
+        cookie.set_partitioned(true);
+    }
+}
+
+/// 生成登录流程的 state 绑定 cookie，值为签入 state 的 nonce
+///
+/// 回调是从授权服务器跳转回来的跨站导航，SameSite 必须为 Lax 才会携带。
+/// 同一浏览器同时发起多个登录时只有最后一个能完成
+pub fn oauth_state_cookie(config: &CookieConfig, nonce: String, max_age: time::Duration) -> Cookie<'static> {
+    flow_cookie(config, OAUTH_STATE_COOKIE, nonce, max_age)
+}
+
+/// 生成保存 PKCE code_verifier 的 cookie，属性与 state 绑定 cookie 相同
+pub fn pkce_verifier_cookie(config: &CookieConfig, verifier: String, max_age: time::Duration) -> Cookie<'static> {
+    flow_cookie(config, PKCE_VERIFIER_COOKIE, verifier, max_age)
+}
+
+/// 清除登录流程使用的 cookie（state 绑定与 PKCE）
+pub fn remove_flow_cookies(config: &CookieConfig, jar: CookieJar) -> CookieJar {
+    [OAUTH_STATE_COOKIE, PKCE_VERIFIER_COOKIE].into_iter().fold(jar, |jar, name| {
+        let mut cookie = Cookie::from(name);
+        apply_attributes(config, &mut cookie);
+        cookie.set_same_site(SameSite::Lax);
+        jar.remove(cookie)
+    })
+}
+
+fn flow_cookie(config: &CookieConfig, name: &'static str, value: String, max_age: time::Duration) -> Cookie<'static> {
+    let mut cookie = Cookie::new(name, value);
+    apply_attributes(config, &mut cookie);
+    cookie.set_same_site(SameSite::Lax);
+    cookie.set_http_only(true);
+    cookie.set_max_age(max_age);
+    cookie
+}
+
+fn chunk_name(index: usize) -> String {
+    format!("{}.{}", SESSION_COOKIE, index)
+}
+
+fn session_cookie(config: &CookieConfig, name: String, value: String, expires: time::OffsetDateTime) -> Cookie<'static> {
+    let mut cookie = Cookie::new(name, value);
+    apply_attributes(config, &mut cookie);
+    cookie.set_http_only(true);
+    cookie.set_expires(expires);
+    cookie
+}
+
+/// 从请求 cookie 中读取会话 token
+///
+/// 优先读取 `access_token`，不存在时按顺序拼接 `access_token.0`、`access_token.1`……
+pub fn session_token(jar: &CookieJar) -> Option<String> {
+    if let Some(cookie) = jar.get(SESSION_COOKIE) {
+        return Some(cookie.value().to_string());
+    }
+    let token: String = (0..)
+        .map_while(|index| jar.get(&chunk_name(index)).map(|cookie| cookie.value().to_string()))
+        .collect();
+    (!token.is_empty()).then_some(token)
+}
+
+/// 清除会话 cookie 及请求中携带的所有分块
+pub fn remove_session(config: &CookieConfig, jar: CookieJar) -> CookieJar {
+    let chunk_prefix = format!("{}.", SESSION_COOKIE);
+    let chunks: Vec<String> = jar.iter()
+        .map(|cookie| cookie.name().to_string())
+        .filter(|name| name.starts_with(&chunk_prefix))
+        .collect();
+
+    let mut removal = Cookie::from(SESSION_COOKIE);
+    apply_attributes(config, &mut removal);
+    let mut jar = jar.remove(removal);
+    for name in chunks {
+        let mut removal = Cookie::from(name);
+        apply_attributes(config, &mut removal);
+        jar = jar.remove(removal);
+    }
+    jar
+}
+
+/// 下发会话 cookie，并清除旧会话遗留的 cookie
+///
+/// token 放不进单个 cookie 且开启了分块时拆分为 `access_token.N`。
+/// 未开启分块或超过 `max_chunks` 仍放不下时返回错误，而不是下发会被浏览器丢弃的超长 cookie
+pub fn add_session(
+    config: &CookieConfig,
+    jar: CookieJar,
+    token: String,
+    expires: time::OffsetDateTime,
+) -> Result<CookieJar, String> {
+    let mut jar = remove_session(config, jar);
+    if token.len() <= CHUNK_SIZE {
+        return Ok(jar.add(session_cookie(config, SESSION_COOKIE.to_string(), token, expires)));
+    }
+
+    let chunk_count = token.len().div_ceil(CHUNK_SIZE);
+    if chunk_count > config.max_chunks {
+        return Err(format!(
+            "Session token too large: {} bytes needs {} cookies, at most {} allowed (cookie.max_chunks)",
+            token.len(), chunk_count, config.max_chunks
+        ));
+    }
+    // token 为 JWT（ASCII 字符），可以直接按字节切分
+    for (index, chunk) in token.as_bytes().chunks(CHUNK_SIZE).enumerate() {
+        let chunk = String::from_utf8_lossy(chunk).into_owned();
+        jar = jar.add(session_cookie(config, chunk_name(index), chunk, expires));
+    }
+    Ok(jar)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expires() -> time::OffsetDateTime {
+        time::OffsetDateTime::now_utc() + time::Duration::hours(1)
+    }
+
+    #[test]
+    fn oversize_token_without_chunking_is_an_error() {
+        let config = CookieConfig::default();
+        let token = "a".repeat(CHUNK_SIZE + 1);
+        assert!(add_session(&config, CookieJar::new(), token, expires()).is_err());
+    }
+
+    #[test]
+    fn oversize_token_is_chunked_when_enabled() {
+        let config = CookieConfig { max_chunks: 2, ..Default::default() };
+        let token = "a".repeat(CHUNK_SIZE + 1);
+        let jar = add_session(&config, CookieJar::new(), token, expires()).ok().unwrap();
+        assert!(jar.get(&chunk_name(0)).is_some());
+        assert!(jar.get(&chunk_name(1)).is_some());
+    }
+}
@@ -0,0 +1,84 @@
+use axum::{
+    extract::{Request, State},
+    http::Method,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{AppState, config::CookieConfig, cookies, error::ApiError, oauth};
+
+/// CSRF cookie 名称（前端可读取）
+pub const CSRF_COOKIE: &str = "csrf_token";
+/// 前端需要回传的 CSRF 请求头
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+
+/// 生成新的 CSRF cookie
+///
+/// 该 cookie 不能设置 HttpOnly，前端需要读取它并放入请求头
+pub fn new_csrf_cookie(config: &CookieConfig, expires: time::OffsetDateTime) -> Cookie<'static> {
+    let mut cookie = Cookie::new(CSRF_COOKIE, Uuid::new_v4().simple().to_string());
+    cookies::apply_attributes(config, &mut cookie);
+    cookie.set_http_only(false);
+    cookie.set_expires(expires);
+    cookie
+}
+
+/// 续期 CSRF cookie，保留前端已持有的 token 值（没有时生成新的）
+pub fn renew_csrf_cookie(config: &CookieConfig, jar: &CookieJar, expires: time::OffsetDateTime) -> Cookie<'static> {
+    let value = jar.get(CSRF_COOKIE)
+        .map(|cookie| cookie.value().to_string())
+        .unwrap_or_else(|| Uuid::new_v4().simple().to_string());
+    let mut cookie = Cookie::new(CSRF_COOKIE, value);
+    cookies::apply_attributes(config, &mut cookie);
+    cookie.set_http_only(false);
+    cookie.set_expires(expires);
+    cookie
+}
+
+/// 清除 CSRF cookie
+pub fn removal_cookie(config: &CookieConfig) -> Cookie<'static> {
+    let mut cookie = Cookie::from(CSRF_COOKIE);
+    cookies::apply_attributes(config, &mut cookie);
+    cookie
+}
+
+/// 常量时间比较，避免通过响应时间猜测 token
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// CSRF 校验中间件（double-submit cookie）
+///
+/// 对 POST/PUT/PATCH/DELETE 请求，要求 `X-CSRF-Token` 请求头与 `csrf_token` cookie 一致。
+/// GET 等安全方法不做校验。
+pub async fn csrf_middleware(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    request: Request,
+    next: Next,
+) -> Response {
+    // 使用 Authorization 请求头认证的请求不依赖 cookie，不存在 CSRF 风险
+    let is_safe = matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    if is_safe || !state.config().server.csrf_protection || oauth::bearer_token(request.headers()).is_some() {
+        return next.run(request).await;
+    }
+
+    let header = request
+        .headers()
+        .get(CSRF_HEADER)
+        .and_then(|value| value.to_str().ok());
+    let valid = match (jar.get(CSRF_COOKIE), header) {
+        (Some(cookie), Some(header)) => constant_time_eq(cookie.value().as_bytes(), header.as_bytes()),
+        _ => false,
+    };
+
+    if !valid {
+        return ApiError::forbidden("CSRF token missing or invalid")
+            .with_code("csrf_failed")
+            .into_response();
+    }
+    next.run(request).await
+}
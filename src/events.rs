@@ -0,0 +1,79 @@
+use axum::{
+    extract::State,
+    response::IntoResponse,
+    Json,
+};
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use crate::AppState;
+
+/// 一条调试事件
+///
+/// 只记录身份标识与概要信息，不会包含访问令牌、授权码等敏感数据
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    /// 事件时间（Unix 时间戳，毫秒）
+    pub timestamp: u64,
+    pub kind: &'static str,
+    pub provider: Option<String>,
+    pub subject: Option<String>,
+    pub detail: Option<String>,
+}
+
+/// 最近事件的环形缓冲区
+///
+/// 写入只在锁内做一次 push/pop，临界区极短，不会成为热路径上的竞争点
+pub struct EventLog {
+    buffer: Mutex<VecDeque<Event>>,
+    capacity: usize,
+}
+
+impl EventLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buffer: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+        }
+    }
+
+    /// 记录一条事件，缓冲区已满时丢弃最早的事件
+    pub fn record(&self, kind: &'static str, provider: Option<&str>, subject: Option<&str>, detail: Option<String>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let event = Event {
+            timestamp: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            kind,
+            provider: provider.map(str::to_string),
+            subject: subject.map(str::to_string),
+            detail,
+        };
+
+        let mut buffer = self.buffer.lock().expect("event log lock poisoned");
+        if buffer.len() >= self.capacity {
+            buffer.pop_front();
+        }
+        buffer.push_back(event);
+    }
+
+    /// 获取所有事件（从新到旧）
+    pub fn snapshot(&self) -> Vec<Event> {
+        let buffer = self.buffer.lock().expect("event log lock poisoned");
+        buffer.iter().rev().cloned().collect()
+    }
+}
+
+/// 查看最近的事件（仅管理员）
+pub async fn list_events(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(serde_json::json!({
+        "events": state.events.snapshot(),
+    }))
+}
@@ -3,13 +3,12 @@
 
 use axum::{
     extract::{Multipart, Path, Query, State},
-    http::StatusCode,
     response::IntoResponse,
     Json,
 };
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use serde_json::json;
-use crate::oauth::AuthUser;
+use crate::{error::ApiError, oauth::{AuthUser, OptionalAuthUser}, upload};
 
 // ============= 示例 1: 简单的用户资料 API =============
 
@@ -21,7 +20,7 @@ pub async fn get_profile(user: AuthUser) -> impl IntoResponse {
         "uid": user.uid,
         "nickname": user.nickname,
         "email": user.email,
-        "players": user.players
+        "players": user.players()
     }))
 }
 
@@ -33,13 +32,18 @@ pub async fn get_profile(user: AuthUser) -> impl IntoResponse {
 pub async fn upload_file(
     user: AuthUser,
     mut multipart: Multipart,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+) -> Result<impl IntoResponse, ApiError> {
     let mut uploaded_files = Vec::new();
     
-    while let Some(field) = multipart.next_field().await.unwrap() {
+    // 请求体格式错误时返回 400，而不是让 handler panic
+    while let Some(field) = multipart.next_field().await
+        .map_err(upload::malformed_multipart)?
+    {
+        // 正式的上传接口（upload::upload_file）会按 `upload.allowed_field_names` 拒绝其它字段
         let name = field.name().unwrap_or("unknown").to_string();
         let filename = field.file_name().unwrap_or("unnamed").to_string();
-        let data = field.bytes().await.unwrap();
+        let data = field.bytes().await
+            .map_err(|e| upload::malformed_multipart(e).with_field("field", name.as_str()))?;
         
         // 这里添加你的文件保存逻辑
         // save_file_to_storage(user.uid, &filename, &data).await?;
@@ -107,14 +111,14 @@ pub async fn list_user_files(
 pub async fn get_file(
     user: AuthUser,
     Path(file_id): Path<u64>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+) -> Result<impl IntoResponse, ApiError> {
     // 这里添加你的数据库查询逻辑
     // let file = db.get_file(file_id).await
-    //     .ok_or_else(|| (StatusCode::NOT_FOUND, "File not found".to_string()))?;
+    //     .ok_or_else(|| ApiError::not_found("File not found"))?;
     
     // 权限检查示例
     // if file.owner_uid != user.uid {
-    //     return Err((StatusCode::FORBIDDEN, "Access denied".to_string()));
+    //     return Err(ApiError::forbidden("Access denied"));
     // }
     
     Ok(Json(json!({
@@ -141,9 +145,9 @@ pub struct AppState {
 /// 结合 State 和 AuthUser
 pub async fn update_settings(
     user: AuthUser,
-    State(state): State<Arc<AppState>>,
-    Json(payload): Json<serde_json::Value>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    State(_state): State<Arc<AppState>>,
+    Json(_payload): Json<serde_json::Value>,
+) -> Result<impl IntoResponse, ApiError> {
     tracing::info!(
         "用户 {} (UID: {}) 正在更新设置",
         user.nickname,
@@ -151,7 +155,7 @@ pub async fn update_settings(
     );
     
     // 这里添加你的数据库更新逻辑
-    // db.update_user_settings(user.uid, &payload).await?;
+    // db.update_user_settings(user.uid, &_payload).await?;
     
     Ok(Json(json!({
         "success": true,
@@ -164,9 +168,10 @@ pub async fn update_settings(
 
 /// 获取公开内容
 /// 
-/// 支持可选认证：未登录用户也可以访问，但登录用户会看到个性化内容
+/// 支持可选认证：未登录用户也可以访问，但登录用户会看到个性化内容。
+/// OptionalAuthUser 自行校验会话，无需挂在 auth_middleware 之后
 pub async fn get_public_content(
-    user: Option<AuthUser>, // 使用 Option 使认证变为可选
+    OptionalAuthUser(user): OptionalAuthUser, // token 缺失或无效时为 None
 ) -> impl IntoResponse {
     match user {
         Some(user) => Json(json!({
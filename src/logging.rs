@@ -0,0 +1,95 @@
+use tracing::{Level, Metadata};
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::{
+    Layer, Registry,
+    filter::LevelFilter,
+    fmt::writer::MakeWriterExt,
+    layer::SubscriberExt,
+    reload,
+    util::SubscriberInitExt,
+};
+
+use crate::config::{LogRotation, LoggingConfig};
+
+/// 审计日志使用的 tracing target
+const AUDIT_TARGET: &str = "audit";
+/// TraceLayer 输出访问日志使用的 tracing target
+const ACCESS_TARGET: &str = "tower_http::trace";
+
+type FileLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// 文件日志的句柄，读取配置后再启用
+pub struct FileLogs {
+    handle: reload::Handle<Option<FileLayer>, Registry>,
+    /// 后台写入线程的守卫，丢弃时会写完剩余日志
+    guards: Vec<WorkerGuard>,
+}
+
+/// 初始化日志：终端输出立即生效，文件输出等配置加载后由 [`FileLogs::enable`] 启用
+pub fn init() -> FileLogs {
+    let (file_layer, handle) = reload::Layer::new(None::<FileLayer>);
+    tracing_subscriber::registry()
+        .with(file_layer)
+        .with(tracing_subscriber::fmt::layer()
+            .with_target(false)
+            .with_level(true))
+        .with(LevelFilter::DEBUG)
+        .init();
+    FileLogs { handle, guards: Vec::new() }
+}
+
+impl FileLogs {
+    /// 按配置把审计日志与访问日志写入滚动文件
+    ///
+    /// 写入在后台线程完成，不会阻塞请求处理
+    pub fn enable(&mut self, config: &LoggingConfig) -> anyhow::Result<()> {
+        let Some(directory) = &config.directory else {
+            return Ok(());
+        };
+
+        let mut layers = Vec::new();
+        for (prefix, target) in [("audit", AUDIT_TARGET), ("access", ACCESS_TARGET)] {
+            let mut builder = RollingFileAppender::builder()
+                .rotation(rotation(config.rotation))
+                .filename_prefix(prefix)
+                .filename_suffix("log");
+            if let Some(max_files) = max_log_files(config) {
+                builder = builder.max_log_files(max_files);
+            }
+            let (writer, guard) = tracing_appender::non_blocking(builder.build(directory)?);
+            self.guards.push(guard);
+
+            // reload 层中不能使用按层过滤，改为在写入时按 target 过滤
+            let writer = writer.with_filter(move |meta: &Metadata<'_>| {
+                meta.target().starts_with(target) && *meta.level() <= Level::INFO
+            });
+            layers.push(tracing_subscriber::fmt::layer()
+                .with_writer(writer)
+                .with_ansi(false)
+                .boxed());
+        }
+
+        self.handle.reload(Some(layers.boxed()))?;
+        Ok(())
+    }
+}
+
+fn rotation(rotation: LogRotation) -> Rotation {
+    match rotation {
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+        LogRotation::Never => Rotation::NEVER,
+    }
+}
+
+/// 按保留天数换算需要保留的文件个数
+fn max_log_files(config: &LoggingConfig) -> Option<usize> {
+    match (config.rotation, config.retain_days) {
+        (_, 0) | (LogRotation::Never, _) => None,
+        (LogRotation::Hourly, days) => Some(days * 24),
+        (LogRotation::Daily, days) => Some(days),
+    }
+}
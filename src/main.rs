@@ -1,29 +1,72 @@
-use axum::{routing::get, Router};
-use hmac::Hmac;
-use hmac::digest::KeyInit;
-use sha2::Sha256;
-use tower_http::trace::{self, TraceLayer};
-use std::{net::SocketAddr, path::Path};
-use std::sync::Arc;
-use tracing::{Level, error, info, warn};
-use tracing_subscriber;
+use axum::{extract::{DefaultBodyLimit, Request}, handler::Handler, routing::{delete, get, post}, Json, Router, ServiceExt};
+use arc_swap::ArcSwap;
+use tower::Layer;
+use axum::http::{HeaderName, HeaderValue, Method, header};
+use tower_http::{cors::CorsLayer, decompression::RequestDecompressionLayer, trace::{self, TraceLayer}};
+use std::{net::{IpAddr, SocketAddr}, path::Path};
+use std::{convert::Infallible, sync::Arc, time::Duration};
+use tokio::sync::Notify;
+use tracing::{Level, debug, error, info, warn};
 use anyhow::Result;
 
+use crate::admin::ReloadStatus;
 use crate::config::{Config, OAuthProviderConfig};
+use crate::events::EventLog;
+use crate::signing::Signer;
+use crate::storage::StorageBackend;
+use crate::upload::UploadState;
+use crate::rate_limit::RateLimiter;
+use crate::maintenance::MaintenanceState;
+use crate::oauth::{
+    flow::PendingFlows, health::ProviderHealth, http::OutboundClients,
+    preflight::{self, ProviderReachability}, session::SessionRegistry,
+};
 
 mod static_content;
 mod oauth;
 mod config;
+mod maintenance;
+mod admin;
+mod account;
+mod csrf;
+mod cookies;
+mod events;
+mod trailing_slash;
+mod problem;
+mod signing;
+mod error;
+mod logging;
+mod upload;
+mod rate_limit;
+mod storage;
+// 示例代码不挂载到路由上，编译它只是为了保证示例与接口保持一致
+#[allow(dead_code)]
+mod example_handlers;
 
 const CONFIG_FILE: &str = "config.yml";
 
 pub struct AppState {
-    pub config: Config,
+    config: ArcSwap<Config>,
+    pub maintenance: MaintenanceState,
+    pub pending_flows: PendingFlows,
+    pub provider_health: ProviderHealth,
+    pub provider_reachability: ProviderReachability,
+    pub events: EventLog,
+    pub sessions: SessionRegistry,
+    pub reload_status: ReloadStatus,
+    /// 上传文件的存储后端（启动时按配置选择）
+    pub storage: Arc<dyn StorageBackend>,
+    pub uploads: UploadState,
+    /// 登录、回调与上传共用的限流器（启动时按配置选择后端）
+    pub rate_limiter: RateLimiter,
+    /// 访问 OAuth 提供者的 HTTP 客户端（重新加载配置时重建）
+    http_clients: ArcSwap<OutboundClients>,
     
-    secret_key: Hmac<Sha256>
+    signer: Signer,
 }
 
 impl AppState {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         // 检查配置文件是否存在
         if !Path::new(CONFIG_FILE).exists() {
@@ -53,67 +96,284 @@ impl AppState {
             }
         };
 
-        let secret_key = Hmac::<Sha256>::new_from_slice(app_config.oauth.secret_string.as_bytes())
-            .expect("HMAC can take key of any size");
+        let errors = app_config.validate();
+        if !errors.is_empty() {
+            for e in &errors {
+                error!("配置校验失败: {}", e);
+            }
+            std::process::exit(1);
+        }
+        for w in app_config.warnings() {
+            warn!("配置警告: {}", w);
+        }
+
+        let signer = Signer::new(&app_config.oauth);
+
+        let maintenance = MaintenanceState::new(
+            app_config.server.maintenance_mode,
+            app_config.server.maintenance_message.clone(),
+        );
+        if maintenance.is_enabled() {
+            warn!("服务器以维护模式启动");
+        }
+
+        let pending_flows = PendingFlows::new(&app_config.oauth.pending_flows);
+        let events = EventLog::new(app_config.server.event_buffer_size);
+
+        let http_clients = match OutboundClients::new(&app_config) {
+            Ok(client) => client,
+            Err(e) => {
+                error!("出站 HTTP 客户端创建失败: {:?}", e);
+                std::process::exit(1);
+            }
+        };
+        debug!("出站 TLS 策略: {:?}", app_config.outbound);
+
+        let storage = storage::create_storage_backend(&app_config.server);
+        let rate_limiter = match rate_limit::create_rate_limit_backend(&app_config.rate_limit.backend) {
+            Ok(backend) => RateLimiter::new(backend),
+            Err(e) => {
+                error!("限流后端创建失败: {:?}", e);
+                std::process::exit(1);
+            }
+        };
+
+        AppState {
+            config: ArcSwap::from_pointee(app_config),
+            maintenance,
+            pending_flows,
+            provider_health: ProviderHealth::default(),
+            provider_reachability: ProviderReachability::default(),
+            events,
+            sessions: SessionRegistry::default(),
+            reload_status: ReloadStatus::default(),
+            storage,
+            uploads: UploadState::default(),
+            rate_limiter,
+            http_clients: ArcSwap::from_pointee(http_clients),
+            signer,
+        }
+    }
 
-        AppState { config: app_config, secret_key }
+    /// 获取当前配置的快照
+    pub fn config(&self) -> Arc<Config> {
+        self.config.load_full()
     }
 
+    /// 原子地替换当前配置
+    pub fn swap_config(&self, config: Config) {
+        self.config.store(Arc::new(config));
+    }
+
+    /// 获取访问提供者使用的 HTTP 客户端
+    pub fn http_client(&self, provider_name: &str) -> reqwest::Client {
+        self.http_clients.load().for_provider(provider_name).clone()
+    }
+
+    /// 获取不属于任何提供者的出站请求使用的 HTTP 客户端
+    pub fn default_http_client(&self) -> reqwest::Client {
+        self.http_clients.load().default_client().clone()
+    }
+
+    /// 替换出站 HTTP 客户端
+    pub fn swap_http_clients(&self, clients: OutboundClients) {
+        self.http_clients.store(Arc::new(clients));
+    }
 
-    /// 获取重定向 URL
+
+    /// 获取重定向 URL（提供者配置了 `callback_path` 时使用自定义路径）
     pub fn get_redirect_uri(&self, provider: &str) -> String {
-        format!("{}/api/oauth/{}/callback", self.config.oauth.prefix_url, provider)
+        let config = self.config();
+        match config.oauth.providers.get(provider).and_then(|p| p.callback_path.as_deref()) {
+            Some(path) => format!("{}{}", config.oauth.prefix_url, path),
+            None => format!("{}/api/oauth/{}/callback", config.oauth.prefix_url, provider),
+        }
     }
 
     /// 获取所有启用的提供者
-    pub fn get_enabled_providers(&self) -> Vec<(String, &OAuthProviderConfig)> {
-        self.config.oauth.providers
+    pub fn get_enabled_providers(&self) -> Vec<(String, OAuthProviderConfig)> {
+        self.config().oauth.providers
             .iter()
             .filter(|(_, config)| config.enabled)
-            .map(|(name, config)| (name.clone(), config))
+            .map(|(name, config)| (name.clone(), config.clone()))
             .collect()
     }
 
     /// 获取特定提供者配置
-    pub fn get_provider(&self, name: &str) -> Option<&OAuthProviderConfig> {
-        self.config.oauth.providers.get(name)
+    pub fn get_provider(&self, name: &str) -> Option<OAuthProviderConfig> {
+        self.config().oauth.providers.get(name).cloned()
+    }
+
+    /// 判断地址是否位于受信任的内网地址段
+    pub fn is_trusted_ip(&self, ip: IpAddr) -> bool {
+        self.config().server.trusted_cidrs.iter().any(|net| net.contains(&ip))
     }
 
-    pub fn secret(&self) -> &Hmac<Sha256> {
-        &self.secret_key
+    pub fn signer(&self) -> &Signer {
+        &self.signer
+    }
+
+    /// 生成机器可读的启动摘要
+    fn startup_summary(&self, addr: SocketAddr) -> serde_json::Value {
+        let config = self.config();
+        let providers: Vec<_> = self.get_enabled_providers()
+            .into_iter()
+            .map(|(name, provider)| serde_json::json!({
+                "name": name,
+                "type": provider.provider_type,
+                "visibility": provider.visibility,
+                "login_url": format!("{}/api/oauth/{}/login", config.oauth.prefix_url, name),
+                "callback_url": self.get_redirect_uri(&name),
+            }))
+            .collect();
+
+        serde_json::json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "bind": addr.to_string(),
+            "prefix_url": config.oauth.prefix_url,
+            "providers": providers,
+            "features": {
+                "maintenance_mode": self.maintenance.is_enabled(),
+                "csrf_protection": config.server.csrf_protection,
+                "trailing_slash": config.server.trailing_slash,
+                "problem_details": config.server.problem_details,
+                "tls": config.server.tls.is_some(),
+                "cors": !config.server.cors_allowed_origins.is_empty(),
+                "single_session_per_user": config.oauth.single_session_per_user,
+                "sort_by_health": config.oauth.sort_by_health,
+                "refresh_grace_period_secs": config.oauth.refresh_grace_period_secs,
+                "refresh_ahead_secs": config.oauth.refresh_ahead_secs,
+                "max_session_lifetime_secs": config.oauth.max_session_lifetime_secs,
+                "step_up_max_age_secs": config.oauth.step_up_max_age_secs,
+                "callback_success_mode": config.oauth.callback_success_mode,
+            },
+        })
     }
 }
 
+/// 存活检查，不访问任何提供者，可以频繁轮询
+async fn health() -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "status": "ok",
+        "version": env!("CARGO_PKG_VERSION"),
+    }))
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // 初始化 tracing 日志
-    tracing_subscriber::fmt()
-        .with_max_level(Level::DEBUG)
-        .with_target(false)
-        .with_level(true)
-        .init();
+    let mut file_logs = logging::init();
     
     let app_state = Arc::new(AppState::new());
+    if let Err(e) = file_logs.enable(&app_state.config().logging) {
+        error!("无法启用日志文件: {:?}", e);
+        std::process::exit(1);
+    }
 
     // 需要认证的路由
     let protected_routes = Router::new()
-        .route("/api/user", get(oauth::get_user))
+        .route("/api/user", get(oauth::get_user).delete(account::delete_account))
+        .route("/api/user/export", get(account::export_user))
+        .route("/api/user/profiles", get(oauth::get_profiles))
+        // 上传接口解压 Content-Encoding: gzip 的请求体；解压在外层进行，
+        // 因此请求体大小限制作用于解压后的数据，可以防止压缩炸弹
+        .route("/api/upload", post(upload::upload_file)
+            .layer::<_, Infallible>(DefaultBodyLimit::max(app_state.config().server.max_upload_bytes))
+            .layer(RequestDecompressionLayer::new()))
+        .route("/api/files", get(upload::list_files))
+        .route("/api/files/{file_id}", delete(upload::delete_file))
+        // 维护模式下拒绝写请求
+        .layer(axum::middleware::from_fn_with_state(
+            app_state.clone(),
+            maintenance::maintenance_guard
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            app_state.clone(),
+            csrf::csrf_middleware
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            app_state.clone(),
+            oauth::auth_middleware
+        ));
+
+    // 登出所有会话：吊销被盗用的会话属于安全操作，维护期间也必须可用，因此不经过维护模式守卫
+    let logout_all_routes = Router::new()
+        .route("/api/logout/all", post(oauth::logout_all))
+        .layer(axum::middleware::from_fn_with_state(
+            app_state.clone(),
+            csrf::csrf_middleware
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            app_state.clone(),
+            oauth::auth_middleware
+        ));
+
+    // 管理员路由（先认证，再检查管理员权限）
+    let admin_routes = Router::new()
+        .route("/api/admin/maintenance", post(maintenance::set_status))
+        .route("/api/admin/reload", get(admin::reload_status).post(admin::reload_config))
+        .route("/api/admin/events", get(events::list_events))
+        .layer(axum::middleware::from_fn_with_state(
+            app_state.clone(),
+            admin::admin_middleware
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            app_state.clone(),
+            csrf::csrf_middleware
+        ))
         .layer(axum::middleware::from_fn_with_state(
             app_state.clone(),
             oauth::auth_middleware
         ));
     
+    // 主动刷新会话（不经过 auth_middleware，以便刷新宽限期内已过期的会话）
+    let refresh_routes = Router::new()
+        .route("/api/token/refresh", post(oauth::refresh))
+        .layer(axum::middleware::from_fn_with_state(
+            app_state.clone(),
+            csrf::csrf_middleware
+        ));
+
+    // 使用自定义回调路径的提供者
+    let custom_callback_routes = app_state.config().oauth.providers
+        .iter()
+        .filter_map(|(name, provider)| provider.callback_path.clone().map(|path| (name.clone(), path)))
+        .fold(Router::new(), |router, (name, path)| {
+            router.route(&path, oauth::custom_callback_route(name))
+        });
+
     // 创建路由
-    let app = Router::new()
+    let mut app = Router::new()
         // OAuth2 提供者列表
         .route("/api/oauth/providers", get(oauth::list_providers))
         // OAuth2 动态路由（支持多个提供者）
         .route("/api/oauth/{provider}/login", get(oauth::login))
         .route("/api/oauth/{provider}/callback", get(oauth::callback))
+        // 会话 token 内省（供内部服务使用）
+        .route("/api/token/introspect", post(oauth::introspect))
+        // 当前会话状态（可选认证）
+        .route("/api/session", get(oauth::get_session))
         // 登出
         .route("/api/logout", get(oauth::logout))
+        // 维护状态
+        .route("/api/maintenance", get(maintenance::get_status))
+        // 上传文件的公开下载地址
+        .route("/api/files/{owner}/{file_id}", get(upload::download_file))
+        // 存活与就绪检查
+        .route("/api/health", get(health))
+        .route("/readyz", get(preflight::readyz))
+        .route("/api/ready", get(preflight::ready))
         // 合并需要认证的路由
+        .merge(custom_callback_routes)
+        .merge(refresh_routes)
         .merge(protected_routes)
+        .merge(logout_all_routes)
+        .merge(admin_routes)
+        // 按需将错误响应转换为 Problem Details
+        .layer(axum::middleware::from_fn_with_state(
+            app_state.clone(),
+            problem::problem_details
+        ))
         .with_state(app_state.clone())
         // API 请求跟踪
         .layer(TraceLayer::new_for_http()
@@ -122,7 +382,12 @@ async fn main() -> Result<()> {
             .on_response(trace::DefaultOnResponse::new().level(Level::INFO))
         )
         // 静态文件服务
-        .fallback(static_content::serve_static);
+        .fallback_service(static_content::serve_static.with_state(app_state.clone()));
+
+    // 前端与 API 不同源时（如开发时的 Vite 服务器）允许跨域携带 cookie 访问
+    if let Some(cors) = cors_layer(&app_state.config().server) {
+        app = app.layer(cors);
+    }
     
     // 如果需要为其他 API 添加认证保护，可以这样做：
     // let protected_routes = Router::new()
@@ -143,14 +408,15 @@ async fn main() -> Result<()> {
     //     .fallback(static_content::serve_static);
 
     // 绑定地址
+    let config = app_state.config();
     let addr = SocketAddr::from((
-        app_state.config.server.host.parse::<std::net::IpAddr>()
+        config.server.host.parse::<IpAddr>()
             .unwrap_or_else(|_| std::net::IpAddr::V4(std::net::Ipv4Addr::new(127, 0, 0, 1))),
-        app_state.config.server.port
+        config.server.port
     ));
     
-    info!("服务器启动地址: http://{}", addr);
-    info!("OAuth 回调基础地址: {}/api/oauth/[provider]/callback", app_state.config.oauth.prefix_url);
+    info!("服务器启动地址: {}://{}", if config.server.tls.is_some() { "https" } else { "http" }, addr);
+    info!("OAuth 回调基础地址: {}/api/oauth/[provider]/callback", config.oauth.prefix_url);
     
     // 显示所有启用的提供者
     let enabled_providers = app_state.get_enabled_providers();
@@ -162,13 +428,75 @@ async fn main() -> Result<()> {
             info!("  - {} ({}): {}/api/oauth/{}/login", 
                 name,
                 provider.provider_type.display_name(),
-                app_state.config.oauth.prefix_url,
+                config.oauth.prefix_url,
                 name
             );
         }
     }
 
-    // 启动服务器
+    // 预检提供者连通性，最多等待 verify_timeout_secs
+    if config.oauth.verify_providers {
+        preflight::verify_providers(&app_state).await;
+    }
+
+    // 证书问题属于部署错误，在绑定端口之前直接退出
+    let tls = match &config.server.tls {
+        Some(tls) => match load_tls_config(tls).await {
+            Ok(rustls) => Some(rustls),
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    
+    // 末尾斜杠处理需要在路由匹配之前进行，因此包裹在整个 Router 外层
+    let app = axum::middleware::from_fn_with_state(
+        config.server.trailing_slash,
+        trailing_slash::normalize_trailing_slash
+    ).layer(app);
+
+    // 输出机器可读的启动摘要
+    if config.server.startup_summary_log || config.server.startup_summary_file.is_some() {
+        let summary = app_state.startup_summary(addr);
+        if config.server.startup_summary_log {
+            info!(target: "startup", "{}", summary);
+        }
+        if let Some(path) = &config.server.startup_summary_file
+            && let Err(e) = std::fs::write(path, summary.to_string())
+        {
+            warn!("写入启动摘要失败 {}: {:?}", path.display(), e);
+        }
+    }
+
+    info!("服务器正在运行...");
+
+    let grace = Duration::from_secs(config.server.shutdown_grace_secs);
+
+    if let Some(tls) = tls {
+        // axum-server 传入的是 hyper 原始请求体，外面再套一层 Router 做转换
+        let make_service = Router::new()
+            .fallback_service(app)
+            .into_make_service_with_connect_info::<SocketAddr>();
+        // axum-server 自带宽限期处理，超时后会直接断开剩余连接
+        let handle = axum_server::Handle::new();
+        tokio::spawn({
+            let handle = handle.clone();
+            async move {
+                shutdown_signal().await;
+                info!("正在关闭服务器...");
+                handle.graceful_shutdown(Some(grace));
+            }
+        });
+        axum_server::bind_rustls(addr, tls)
+            .handle(handle)
+            .serve(make_service)
+            .await
+            .inspect_err(|e| error!("绑定地址失败 {}: {:?}", addr, e))?;
+        return Ok(());
+    }
+
     let listener = match tokio::net::TcpListener::bind(addr).await {
         Ok(listener) => listener,
         Err(e) => {
@@ -176,11 +504,96 @@ async fn main() -> Result<()> {
             std::process::exit(1);
         }
     };
-    
-    info!("服务器正在运行...");
-    
-    axum::serve(listener, app).await
-        .inspect_err(|e| error!("Error: {e:?}"))?;
+
+    // 收到关闭信号后停止接受新连接，等待进行中的请求完成，超过宽限期则直接退出
+    let shutdown = Arc::new(Notify::new());
+    let serve = axum::serve(listener, ServiceExt::<Request>::into_make_service_with_connect_info::<SocketAddr>(app))
+        .with_graceful_shutdown({
+            let shutdown = shutdown.clone();
+            async move {
+                shutdown_signal().await;
+                info!("正在关闭服务器...");
+                shutdown.notify_one();
+            }
+        });
+    tokio::select! {
+        result = async { serve.await } => {
+            result.inspect_err(|e| error!("Error: {e:?}"))?;
+        }
+        _ = async { shutdown.notified().await; tokio::time::sleep(grace).await } => {
+            warn!("关闭宽限期已过，仍有请求未完成，强制退出");
+        }
+    }
 
     Ok(())
 }
+
+/// 按 `cors_allowed_origins` 创建 CORS 层，列表为空时返回 `None`（只允许同源访问）
+///
+/// 会话依赖 cookie，因此允许携带凭据；此时不能使用通配符，方法与请求头都需要逐一列出
+fn cors_layer(config: &config::ServerConfig) -> Option<CorsLayer> {
+    if config.cors_allowed_origins.is_empty() {
+        return None;
+    }
+    let origins: Vec<HeaderValue> = config.cors_allowed_origins
+        .iter()
+        .filter_map(|origin| HeaderValue::from_str(origin).ok())
+        .collect();
+    Some(CorsLayer::new()
+        .allow_origin(origins)
+        .allow_credentials(true)
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE])
+        .allow_headers([
+            header::CONTENT_TYPE,
+            header::AUTHORIZATION,
+            HeaderName::from_bytes(csrf::CSRF_HEADER.as_bytes()).expect("CSRF header name is valid"),
+            HeaderName::from_bytes(upload::STORAGE_CLASS_HEADER.as_bytes()).expect("storage class header name is valid"),
+        ]))
+}
+
+/// 读取 HTTPS 证书与私钥
+///
+/// 先分别读取两个文件，使缺失或无权限时的错误信息能指明是哪个文件
+async fn load_tls_config(tls: &config::TlsConfig) -> Result<axum_server::tls_rustls::RustlsConfig, String> {
+    let cert = tokio::fs::read(&tls.cert_path).await
+        .map_err(|e| format!("无法读取 TLS 证书文件 {}: {}", tls.cert_path.display(), e))?;
+    let key = tokio::fs::read(&tls.key_path).await
+        .map_err(|e| format!("无法读取 TLS 私钥文件 {}: {}", tls.key_path.display(), e))?;
+    axum_server::tls_rustls::RustlsConfig::from_pem(cert, key).await
+        .map_err(|e| format!(
+            "TLS 证书或私钥无效（{} / {}）: {}",
+            tls.cert_path.display(),
+            tls.key_path.display(),
+            e
+        ))
+}
+
+/// 等待 Ctrl-C 或（Unix 下的）SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("无法监听 Ctrl-C 信号: {:?}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                error!("无法监听 SIGTERM 信号: {:?}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
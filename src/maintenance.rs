@@ -0,0 +1,104 @@
+use axum::{
+    extract::{Request, State},
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use std::sync::{Arc, RwLock, atomic::{AtomicBool, Ordering}};
+use tracing::info;
+
+use crate::{AppState, error::ApiError};
+
+/// 维护模式运行时状态
+///
+/// 初始值来自配置文件，之后可由管理员通过接口切换
+pub struct MaintenanceState {
+    enabled: AtomicBool,
+    message: RwLock<String>,
+}
+
+impl MaintenanceState {
+    pub fn new(enabled: bool, message: String) -> Self {
+        Self {
+            enabled: AtomicBool::new(enabled),
+            message: RwLock::new(message),
+        }
+    }
+
+    /// 是否处于维护模式
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// 获取维护提示信息
+    pub fn message(&self) -> String {
+        self.message.read().expect("maintenance lock poisoned").clone()
+    }
+
+    /// 切换维护模式，可同时更新提示信息
+    pub fn set(&self, enabled: bool, message: Option<String>) {
+        if let Some(message) = message {
+            *self.message.write().expect("maintenance lock poisoned") = message;
+        }
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// 维护模式下返回 503 响应，否则返回 None
+    pub fn reject(&self) -> Option<ApiError> {
+        self.is_enabled()
+            .then(|| ApiError::new(StatusCode::SERVICE_UNAVAILABLE, self.message()).with_code("maintenance"))
+    }
+
+    fn status_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "maintenance": self.is_enabled(),
+            "message": self.message(),
+        })
+    }
+}
+
+/// 管理员切换维护模式的请求体
+#[derive(Deserialize)]
+pub struct MaintenanceToggle {
+    pub enabled: bool,
+    pub message: Option<String>,
+}
+
+/// 获取维护状态（供前端展示）
+pub async fn get_status(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(state.maintenance.status_json())
+}
+
+/// 切换维护模式（仅管理员）
+pub async fn set_status(
+    State(state): State<Arc<AppState>>,
+    Json(toggle): Json<MaintenanceToggle>,
+) -> impl IntoResponse {
+    state.maintenance.set(toggle.enabled, toggle.message);
+    info!("维护模式已{}", if toggle.enabled { "开启" } else { "关闭" });
+    state.events.record(
+        if toggle.enabled { "maintenance_enabled" } else { "maintenance_disabled" },
+        None,
+        None,
+        None,
+    );
+    Json(state.maintenance.status_json())
+}
+
+/// 维护模式守卫中间件
+///
+/// 维护期间拒绝所有写请求（POST/PUT/PATCH/DELETE），读请求照常放行。
+/// `POST /api/logout/all` 不经过这里，维护期间也能吊销会话
+pub async fn maintenance_guard(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let is_read = matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    if !is_read && let Some(rejection) = state.maintenance.reject() {
+        return rejection.into_response();
+    }
+    next.run(request).await
+}
@@ -1,22 +1,15 @@
-use std::time::Duration;
+use std::collections::HashMap;
 
-use super::{OAuthProvider, OAuthProviderType, UnifiedUserInfo};
+use super::{OAuthProvider, OAuthProviderType, ProviderCapabilities, TokenGrant, TokenResponse, UnifiedUserInfo};
+use super::error::{ProviderError, check_token_response, read_json};
 use crate::{config::OAuthProviderConfig, oauth::YggdrasilProfile};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use tracing::debug;
+use tracing::{debug, warn};
 
 use anyhow::Result;
 
 
-#[derive(Deserialize, Debug)]
-struct TokenResponse {
-    access_token: String,
-    token_type: String,
-    #[serde(default)]
-    expires_in: u64,
-}
-
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct BlessingSkinUserInfo {
     pub uid: u64,
@@ -24,6 +17,7 @@ pub struct BlessingSkinUserInfo {
     pub email: String,
 }
 
+#[allow(dead_code)]
 #[derive(Deserialize, Debug, Clone)]
 pub struct BlessingSkinProfile {
     pub pid: i32,
@@ -34,105 +28,201 @@ pub struct BlessingSkinProfile {
     pub last_modified: String,
 }
 
+/// 解析 Blessing Skin 返回的修改时间（`YYYY-MM-DD HH:MM:SS`，按 UTC 处理；也接受 RFC 3339）
+fn parse_last_modified(value: &str) -> Option<u64> {
+    let format = time::macros::format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
+    let datetime = time::PrimitiveDateTime::parse(value, format)
+        .map(|datetime| datetime.assume_utc())
+        .or_else(|_| time::OffsetDateTime::parse(value, &time::format_description::well_known::Rfc3339))
+        .ok()?;
+    u64::try_from(datetime.unix_timestamp()).ok()
+}
+
 pub struct BlessingSkinProvider {
     config: OAuthProviderConfig,
     name: String,
+    client: reqwest::Client,
 }
 
 impl BlessingSkinProvider {
-    pub fn new(config: OAuthProviderConfig, name: String) -> Self {
-        Self { config, name }
+    pub fn new(config: OAuthProviderConfig, name: String, client: reqwest::Client) -> Self {
+        Self { config, name, client }
     }
 }
 
 #[async_trait]
 impl OAuthProvider for BlessingSkinProvider {
-    fn get_authorize_url(&self, redirect_uri: &str, state: &str) -> String {
+    fn get_authorize_url(&self, redirect_uri: &str, state: &str) -> Result<String> {
         let scopes = &self.config.scopes;
         
-        // 从 provider_type 中提取 base URL
-        let base_url = self.config.provider_type.base_url().trim_end_matches('/');
+        let mut url = self.config.provider_type.endpoint("oauth/authorize")?;
+        url.query_pairs_mut()
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", redirect_uri)
+            .append_pair("response_type", "code")
+            .append_pair("state", state)
+            .append_pair("scope", &scopes.join(" "));
         
-        format!(
-            "{}/oauth/authorize?client_id={}&redirect_uri={}&response_type=code&state={}&scope={}",
-            base_url,
-            urlencoding::encode(&self.config.client_id),
-            urlencoding::encode(redirect_uri),
-            state,
-            scopes.join(" ")
-        )
+        Ok(url.into())
     }
 
-    async fn exchange_token(&self, code: &str, redirect_uri: &str) -> Result<(String, Duration)> {
-        let client = reqwest::Client::new();
-        
-        // 从 provider_type 中提取 base URL
-        let base_url = self.config.provider_type.base_url().trim_end_matches('/');
+    /// Blessing Skin 没有标准的 OAuth 登出端点，只使用配置的 `end_session_url`
+    fn end_session_url(&self, post_logout_redirect: &str) -> Option<String> {
+        let mut url = url::Url::parse(self.config.end_session_url.as_deref()?).ok()?;
+        url.query_pairs_mut().append_pair("post_logout_redirect_uri", post_logout_redirect);
+        Some(url.into())
+    }
+
+    fn supports_pkce(&self) -> bool {
+        self.config.pkce.unwrap_or(false)
+    }
+
+    async fn exchange_token(
+        &self,
+        code: &str,
+        redirect_uri: &str,
+        code_verifier: Option<&str>,
+    ) -> Result<TokenGrant, ProviderError> {
+        let client = &self.client;
         
-        let token_data: TokenResponse = client
-            .post(format!("{}/oauth/token", base_url))
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("client_id", &self.config.client_id),
+            ("client_secret", &self.config.client_secret),
+            ("redirect_uri", redirect_uri),
+            ("code", code),
+        ];
+        if let Some(code_verifier) = code_verifier {
+            form.push(("code_verifier", code_verifier));
+        }
+        let response = client
+            .post(self.config.provider_type.endpoint("oauth/token")?)
+            .form(&form)
+            .send().await?;
+        let limit = self.config.max_response_bytes;
+        let token_data: TokenResponse = read_json(check_token_response(response, limit).await?, limit).await?;
+        token_data.check_token_type()?;
+
+        debug!("Token 获取成功");
+        Ok(token_data.into_grant())
+    }
+
+    async fn refresh_token(&self, refresh_token: &str) -> Result<TokenGrant, ProviderError> {
+        let client = &self.client;
+        let scopes = self.config.scopes.join(" ");
+
+        let response = client
+            .post(self.config.provider_type.endpoint("oauth/token")?)
             .form(&[
-                ("grant_type", "authorization_code"),
+                ("grant_type", "refresh_token"),
                 ("client_id", &self.config.client_id),
                 ("client_secret", &self.config.client_secret),
-                ("redirect_uri", redirect_uri),
-                ("code", code),
+                ("refresh_token", refresh_token),
+                ("scope", &scopes),
             ])
-            .send().await?.error_for_status()?
-            .json().await?;
+            .send().await?;
+        let limit = self.config.max_response_bytes;
+        let token_data: TokenResponse = read_json(check_token_response(response, limit).await?, limit).await?;
+        token_data.check_token_type()?;
 
-        debug!("Token 获取成功");
-        Ok((token_data.access_token, Duration::from_secs(token_data.expires_in)))
+        debug!("Token 刷新成功");
+        Ok(token_data.into_grant())
     }
 
-    async fn get_user_info(&self, access_token: &str) -> Result<UnifiedUserInfo> {
-        let client = reqwest::Client::new();
-        
-        // 从 provider_type 中提取 base URL
-        let base_url = match &self.config.provider_type {
-            OAuthProviderType::BlessingSkin(url) => url.as_str(),
-            _ => panic!("Invalid provider type for BlessingSkinProvider"),
+    async fn get_user_info(&self, access_token: &str) -> Result<UnifiedUserInfo, ProviderError> {
+        let client = &self.client;
+        let provider_type = &self.config.provider_type;
+        let limit = self.config.max_response_bytes;
+        let log_failure = |what: &str, e: &ProviderError| {
+            warn!("从 {} ({}) 获取{}失败: {}", self.name, provider_type.base_url(), what, e);
         };
         
-        let user_info: BlessingSkinUserInfo = client
-            .get(format!("{}/api/user", base_url))
+        let response = client
+            .get(provider_type.endpoint("api/user")?)
             .bearer_auth(access_token)
             .send().await?
-            .json().await?;
+            .error_for_status()
+            .map_err(ProviderError::from)
+            .inspect_err(|e| log_failure("用户信息", e))?;
+        let user_info: BlessingSkinUserInfo = read_json(response, limit).await?;
 
         debug!("BlessingSkin 用户信息获取成功: uid={}, nickname={}", user_info.uid, user_info.nickname);
 
         // 获取profiles
-        let profs: Vec<BlessingSkinProfile> = client
-            .get(format!("{}/api/players", base_url))
+        let response = client
+            .get(provider_type.endpoint("api/players")?)
             .bearer_auth(access_token)
             .send().await?
-            .json().await?;
-
+            .error_for_status()
+            .map_err(ProviderError::from)
+            .inspect_err(|e| log_failure("角色列表", e))?;
+        let profs: Vec<BlessingSkinProfile> = read_json(response, limit).await?;
+
+        // 各角色的修改时间，以及所有角色中最近的修改时间
+        let modified_at: HashMap<String, u64> = profs.iter()
+            .filter_map(|p| parse_last_modified(&p.last_modified).map(|time| (p.name.clone(), time)))
+            .collect();
+        let last_modified = modified_at.values().copied().max();
         let names = profs.into_iter().map(|p| p.name).collect::<Vec<String>>();
 
         debug!("Player names: {:?}", names);
 
-        let profiles: Vec<YggdrasilProfile> = client
-            .post(format!("{}/api/yggdrasil/api/profiles/minecraft", base_url))
+        let profiles_response = client
+            .post(provider_type.endpoint("api/yggdrasil/api/profiles/minecraft")?)
             .json(&names)
-            .send().await?
-            .json().await?;
+            .send().await?;
+
+        // 部分 Blessing Skin 站点未启用 Yggdrasil API，此时退回到仅包含角色名的 profile
+        let profiles: Vec<YggdrasilProfile> = if profiles_response.status() == reqwest::StatusCode::NOT_FOUND
+            && self.config.yggdrasil_fallback
+        {
+            warn!("Yggdrasil API not available on {} ({}), using player names only", self.name, provider_type.base_url());
+            names
+                .into_iter()
+                .map(|name| YggdrasilProfile { id: String::new(), name, properties: Vec::new() })
+                .collect()
+        } else {
+            let profiles_response = profiles_response
+                .error_for_status()
+                .map_err(ProviderError::from)
+                .inspect_err(|e| log_failure("Yggdrasil 角色信息", e))?;
+            read_json(profiles_response, limit).await?
+        };
 
         debug!("Profiles: {:?}", profiles);
 
+        let primary_profile = UnifiedUserInfo::select_primary_profile(
+            &profiles,
+            &self.config.primary_profile,
+            |profile| modified_at.get(&profile.name).copied(),
+        );
+
         // 转换为统一格式
+        let uid = user_info.uid.to_string();
         Ok(UnifiedUserInfo {
-            uid: user_info.uid.to_string(),
+            subject: UnifiedUserInfo::make_subject(&self.name, &uid),
+            uid,
             nickname: user_info.nickname,
             email: user_info.email,
             provider: self.name.clone(),
             provider_type: self.provider_type(),
             profiles,
+            avatar_url: None,
+            last_modified,
+            primary_profile,
         })
     }
 
     fn provider_type(&self) -> OAuthProviderType {
         self.config.provider_type.clone()
     }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            refresh_token: true,
+            pkce: self.supports_pkce(),
+            profiles: true,
+            ..Default::default()
+        }
+    }
 }
@@ -0,0 +1,160 @@
+use axum::http::StatusCode;
+use serde::{Deserialize, de::DeserializeOwned};
+use std::fmt;
+
+use crate::error::ApiError;
+
+/// OAuth 提供者调用失败的原因
+#[derive(Debug)]
+pub enum ProviderError {
+    /// 无法连接到上游（请求尚未发出）
+    Connect(String),
+    /// 上游在 `request_timeout_secs` 内没有完成响应
+    Timeout(String),
+    /// 网络错误（连接中断等）
+    Network(String),
+    /// 授权码无效、已过期或已被使用
+    InvalidGrant(String),
+    /// 上游返回了非成功的状态码
+    Upstream(StatusCode),
+    /// 上游响应无法解析
+    Parse(String),
+    /// 上游响应体超过大小上限
+    ResponseTooLarge(usize),
+    /// 上游拒绝了访问令牌
+    Unauthorized,
+    /// 提供者配置错误
+    Config(String),
+    /// 提供者不支持该操作
+    Unsupported(String),
+    /// 上游返回了非 Bearer 类型的令牌
+    UnsupportedTokenType(String),
+}
+
+impl ProviderError {
+    /// 对应返回给客户端的 HTTP 状态码
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Connect(_) | Self::Network(_) | Self::Upstream(_) | Self::Parse(_) | Self::ResponseTooLarge(_) => StatusCode::BAD_GATEWAY,
+            Self::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            Self::InvalidGrant(_) => StatusCode::BAD_REQUEST,
+            Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::Config(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::UnsupportedTokenType(_) => StatusCode::BAD_GATEWAY,
+            Self::Unsupported(_) => StatusCode::NOT_IMPLEMENTED,
+        }
+    }
+
+    /// 返回给前端的错误标识
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Connect(_) => "provider_unreachable",
+            Self::Timeout(_) => "provider_timeout",
+            Self::Network(_) => "provider_network_error",
+            Self::InvalidGrant(_) => "invalid_grant",
+            Self::Upstream(_) => "provider_error",
+            Self::Parse(_) => "provider_bad_response",
+            Self::ResponseTooLarge(_) => "provider_response_too_large",
+            Self::Unauthorized => "provider_unauthorized",
+            Self::Config(_) => "provider_misconfigured",
+            Self::Unsupported(_) => "unsupported_operation",
+            Self::UnsupportedTokenType(_) => "unsupported_token_type",
+        }
+    }
+
+    /// 是否属于上游故障（用于健康统计），用户侧的错误不计入
+    pub fn is_upstream_fault(&self) -> bool {
+        matches!(self, Self::Connect(_) | Self::Timeout(_) | Self::Network(_) | Self::Upstream(_) | Self::Parse(_) | Self::ResponseTooLarge(_))
+    }
+
+    /// 是否可以安全地重试（请求没有到达上游）
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::Connect(_))
+    }
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Connect(e) => write!(f, "Failed to connect to provider: {}", e),
+            Self::Timeout(e) => write!(f, "Provider did not respond in time: {}", e),
+            Self::Network(e) => write!(f, "Network error while contacting provider: {}", e),
+            Self::InvalidGrant(e) => write!(f, "Invalid authorization grant: {}", e),
+            Self::Upstream(status) => write!(f, "Provider responded with status {}", status),
+            Self::Parse(e) => write!(f, "Failed to parse provider response: {}", e),
+            Self::ResponseTooLarge(limit) => write!(f, "Provider response exceeded {} bytes", limit),
+            Self::Unauthorized => write!(f, "Provider rejected the access token"),
+            Self::Config(e) => write!(f, "Provider misconfigured: {}", e),
+            Self::Unsupported(op) => write!(f, "Provider does not support {}", op),
+            Self::UnsupportedTokenType(kind) => write!(f, "Unsupported token type `{}`, only Bearer tokens are supported", kind),
+        }
+    }
+}
+
+impl std::error::Error for ProviderError {}
+
+impl From<reqwest::Error> for ProviderError {
+    fn from(e: reqwest::Error) -> Self {
+        match e.status() {
+            Some(StatusCode::UNAUTHORIZED) => Self::Unauthorized,
+            Some(status) => Self::Upstream(status),
+            None if e.is_timeout() => Self::Timeout(e.to_string()),
+            None if e.is_connect() => Self::Connect(e.to_string()),
+            None if e.is_decode() => Self::Parse(e.to_string()),
+            None => Self::Network(e.to_string()),
+        }
+    }
+}
+
+impl From<ProviderError> for ApiError {
+    fn from(e: ProviderError) -> Self {
+        ApiError::new(e.status_code(), e.to_string()).with_code(e.code())
+    }
+}
+
+impl From<url::ParseError> for ProviderError {
+    fn from(e: url::ParseError) -> Self {
+        Self::Config(e.to_string())
+    }
+}
+
+/// OAuth2 标准错误响应（RFC 6749 5.2）
+#[derive(Deserialize)]
+struct OAuthErrorResponse {
+    error: String,
+    #[serde(default)]
+    error_description: Option<String>,
+}
+
+/// 读取上游响应体并解析为 JSON，响应体超过 `limit` 字节时中止读取
+pub async fn read_json<T: DeserializeOwned>(mut response: reqwest::Response, limit: usize) -> Result<T, ProviderError> {
+    super::http::log_tls(&response);
+    if response.content_length().is_some_and(|len| len > limit as u64) {
+        return Err(ProviderError::ResponseTooLarge(limit));
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > limit {
+            return Err(ProviderError::ResponseTooLarge(limit));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    serde_json::from_slice(&body).map_err(|e| ProviderError::Parse(e.to_string()))
+}
+
+/// 检查令牌端点的响应，将 `invalid_grant` 等错误转换为 ProviderError
+pub async fn check_token_response(response: reqwest::Response, limit: usize) -> Result<reqwest::Response, ProviderError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    match read_json::<OAuthErrorResponse>(response, limit).await {
+        Ok(body) if body.error == "invalid_grant" => Err(ProviderError::InvalidGrant(
+            body.error_description.unwrap_or(body.error),
+        )),
+        _ if status == StatusCode::UNAUTHORIZED => Err(ProviderError::Unauthorized),
+        _ => Err(ProviderError::Upstream(status)),
+    }
+}
@@ -0,0 +1,155 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+use crate::config::PendingFlowsConfig;
+
+/// 并发限制的计数键：(提供者名称, IP)
+type FlowKey = (String, IpAddr);
+
+/// 一个尚未完成回调的登录流程
+struct PendingFlow {
+    key: FlowKey,
+}
+
+#[derive(Default)]
+struct PendingFlowsInner {
+    flows: HashMap<Uuid, PendingFlow>,
+    /// 按创建时间排序的流程（可能包含已完成的流程，淘汰时跳过；
+    /// 超过 `max_total` 的两倍时压缩，长度不会随登录量增长）
+    order: VecDeque<(Uuid, Instant)>,
+    per_ip: HashMap<FlowKey, usize>,
+}
+
+impl PendingFlowsInner {
+    fn remove(&mut self, nonce: &Uuid) -> bool {
+        let Some(flow) = self.flows.remove(nonce) else {
+            return false;
+        };
+        if let Some(count) = self.per_ip.get_mut(&flow.key) {
+            *count -= 1;
+            if *count == 0 {
+                self.per_ip.remove(&flow.key);
+            }
+        }
+        true
+    }
+
+    /// 淘汰最早的一个流程，返回是否有流程被淘汰
+    fn evict_oldest(&mut self) -> bool {
+        while let Some((nonce, _)) = self.order.pop_front() {
+            if self.remove(&nonce) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// 从 `order` 中移除已经完成或被淘汰的流程
+    fn compact(&mut self) {
+        let flows = &self.flows;
+        self.order.retain(|(nonce, _)| flows.contains_key(nonce));
+    }
+
+    /// 清理已超时的流程
+    fn prune_expired(&mut self, ttl: Duration) {
+        while let Some((nonce, created_at)) = self.order.front().copied() {
+            if created_at.elapsed() < ttl {
+                break;
+            }
+            self.order.pop_front();
+            self.remove(&nonce);
+        }
+    }
+}
+
+/// 进行中的 OAuth 登录流程登记表
+///
+/// 限制全局与单个 IP 的并发流程数量，保证内存占用有上限。
+/// 单个 IP 的限制按提供者分别计算，某个提供者上的重试风暴不会影响其它提供者的登录。
+/// 每个 state 只能完成一次回调。
+pub struct PendingFlows {
+    inner: Mutex<PendingFlowsInner>,
+    max_total: usize,
+    max_per_ip: usize,
+    ttl: Duration,
+}
+
+impl PendingFlows {
+    pub fn new(config: &PendingFlowsConfig) -> Self {
+        Self {
+            inner: Mutex::new(PendingFlowsInner::default()),
+            max_total: config.max_total.max(1),
+            max_per_ip: config.max_per_ip,
+            ttl: Duration::from_secs(config.ttl_secs),
+        }
+    }
+
+    /// 登记新的登录流程
+    ///
+    /// 该 IP 在该提供者上的并发流程已达上限时返回 false；全局达到上限时淘汰最早的流程。
+    /// `max_per_ip` 为提供者单独配置的上限，未配置时使用全局默认值
+    pub fn begin(&self, nonce: Uuid, provider: &str, ip: IpAddr, max_per_ip: Option<usize>) -> bool {
+        let mut inner = self.inner.lock().expect("pending flows lock poisoned");
+        inner.prune_expired(self.ttl);
+
+        let key = (provider.to_string(), ip);
+        if inner.per_ip.get(&key).copied().unwrap_or(0) >= max_per_ip.unwrap_or(self.max_per_ip) {
+            return false;
+        }
+        while inner.flows.len() >= self.max_total && inner.evict_oldest() {}
+
+        *inner.per_ip.entry(key.clone()).or_insert(0) += 1;
+        inner.flows.insert(nonce, PendingFlow { key });
+        inner.order.push_back((nonce, Instant::now()));
+        if inner.order.len() > 2 * self.max_total {
+            inner.compact();
+        }
+        true
+    }
+
+    /// 完成登录流程
+    ///
+    /// 流程不存在（已使用、已超时或已被淘汰）或不是在该提供者上发起的时返回 false，
+    /// 提供者不符时流程保留，不影响原提供者上的回调
+    pub fn finish(&self, nonce: &Uuid, provider: &str) -> bool {
+        let mut inner = self.inner.lock().expect("pending flows lock poisoned");
+        inner.prune_expired(self.ttl);
+        if inner.flows.get(nonce).is_none_or(|flow| flow.key.0 != provider) {
+            return false;
+        }
+        inner.remove(nonce)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finished_flows_do_not_grow_order() {
+        let flows = PendingFlows::new(&PendingFlowsConfig { max_total: 4, max_per_ip: 4, ttl_secs: 600 });
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+        for _ in 0..100 {
+            let nonce = Uuid::new_v4();
+            assert!(flows.begin(nonce, "littleskin", ip, None));
+            assert!(flows.finish(&nonce, "littleskin"));
+        }
+        assert!(flows.inner.lock().unwrap().order.len() <= 2 * 4);
+    }
+
+    #[test]
+    fn evicted_flows_do_not_grow_order() {
+        let flows = PendingFlows::new(&PendingFlowsConfig { max_total: 4, max_per_ip: 1, ttl_secs: 600 });
+        for i in 0..100u8 {
+            assert!(flows.begin(Uuid::new_v4(), "littleskin", IpAddr::from([203, 0, 113, i]), None));
+        }
+        let inner = flows.inner.lock().unwrap();
+        assert_eq!(inner.flows.len(), 4);
+        assert!(inner.order.len() <= 2 * 4);
+    }
+}
@@ -0,0 +1,63 @@
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// 连续失败多少次后熔断
+const FAILURE_THRESHOLD: u32 = 3;
+/// 熔断后经过多久进入半开状态
+const COOLDOWN: Duration = Duration::from_secs(60);
+
+/// 提供者健康状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    /// 最近的上游调用成功
+    Healthy,
+    /// 出现过失败但尚未熔断，或熔断冷却结束等待验证
+    Degraded,
+    /// 连续失败达到阈值，已熔断
+    Tripped,
+}
+
+#[derive(Default)]
+struct HealthEntry {
+    consecutive_failures: u32,
+    tripped_at: Option<Instant>,
+}
+
+/// 按提供者名称记录上游调用结果的简易熔断器
+#[derive(Default)]
+pub struct ProviderHealth {
+    entries: Mutex<HashMap<String, HealthEntry>>,
+}
+
+impl ProviderHealth {
+    /// 记录一次成功的上游调用
+    pub fn record_success(&self, provider: &str) {
+        let mut entries = self.entries.lock().expect("provider health lock poisoned");
+        entries.remove(provider);
+    }
+
+    /// 记录一次失败的上游调用
+    pub fn record_failure(&self, provider: &str) {
+        let mut entries = self.entries.lock().expect("provider health lock poisoned");
+        let entry = entries.entry(provider.to_string()).or_default();
+        entry.consecutive_failures += 1;
+        if entry.consecutive_failures >= FAILURE_THRESHOLD {
+            entry.tripped_at = Some(Instant::now());
+        }
+    }
+
+    /// 获取提供者当前的健康状态
+    pub fn status(&self, provider: &str) -> HealthStatus {
+        let entries = self.entries.lock().expect("provider health lock poisoned");
+        match entries.get(provider) {
+            None => HealthStatus::Healthy,
+            Some(HealthEntry { tripped_at: Some(at), .. }) if at.elapsed() < COOLDOWN => HealthStatus::Tripped,
+            Some(_) => HealthStatus::Degraded,
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use anyhow::{Context, Result};
+use reqwest::{Certificate, Client, tls};
+use std::{collections::HashMap, fs, time::Duration};
+use tracing::{debug, info};
+
+use crate::config::{Config, OutboundConfig, PoolConfig, TlsBackend, TlsVersion};
+
+/// 未配置时的连接超时（秒）
+const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 10;
+/// 未配置时的读取超时（秒）
+const DEFAULT_READ_TIMEOUT_SECS: u64 = 30;
+
+/// 出站 HTTP 客户端集合
+///
+/// 所有提供者默认共用同一个客户端，以复用连接池并统一 TLS 策略；
+/// 单独配置了连接池的提供者使用自己的客户端
+pub struct OutboundClients {
+    default: Client,
+    per_provider: HashMap<String, Client>,
+}
+
+impl OutboundClients {
+    /// 根据配置构建所有客户端
+    ///
+    /// CA 证书文件无法读取或格式错误时返回错误，调用方可以继续使用旧的客户端
+    pub fn new(config: &Config) -> Result<Self> {
+        let certs = load_ca_certs(&config.outbound)?;
+        let timeout = Duration::from_secs(config.oauth.request_timeout_secs);
+        let default = build_client(&config.outbound, &config.outbound.pool, &certs, timeout)?;
+        let mut per_provider = HashMap::new();
+        for (name, provider) in &config.oauth.providers {
+            if let Some(pool) = &provider.pool {
+                per_provider.insert(name.clone(), build_client(&config.outbound, &pool.or(&config.outbound.pool), &certs, timeout)?);
+            }
+        }
+        Ok(Self { default, per_provider })
+    }
+
+    /// 获取提供者使用的客户端
+    pub fn for_provider(&self, provider_name: &str) -> &Client {
+        self.per_provider.get(provider_name).unwrap_or(&self.default)
+    }
+
+    /// 获取默认客户端
+    pub fn default_client(&self) -> &Client {
+        &self.default
+    }
+}
+
+/// 读取配置的额外 CA 证书
+fn load_ca_certs(config: &OutboundConfig) -> Result<Vec<Certificate>> {
+    let mut certs = Vec::new();
+    for path in &config.ca_certs {
+        let pem = fs::read(path)
+            .with_context(|| format!("failed to read CA certificate file {}", path.display()))?;
+        let bundle = Certificate::from_pem_bundle(&pem)
+            .with_context(|| format!("malformed CA certificate file {}", path.display()))?;
+        certs.extend(bundle);
+    }
+    if !config.ca_certs.is_empty() {
+        info!("已加载 {} 个自定义 CA 证书", certs.len());
+    }
+    Ok(certs)
+}
+
+/// 根据配置构建出站 HTTP 客户端
+///
+/// `timeout` 为单个请求从发出到读完响应的总时长上限
+fn build_client(config: &OutboundConfig, pool: &PoolConfig, certs: &[Certificate], timeout: Duration) -> reqwest::Result<Client> {
+    let min_tls_version = match config.min_tls_version {
+        TlsVersion::Tls12 => tls::Version::TLS_1_2,
+        TlsVersion::Tls13 => tls::Version::TLS_1_3,
+    };
+    let mut builder = Client::builder()
+        .min_tls_version(min_tls_version)
+        .connect_timeout(Duration::from_secs(config.connect_timeout_secs.unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECS)))
+        .read_timeout(Duration::from_secs(config.read_timeout_secs.unwrap_or(DEFAULT_READ_TIMEOUT_SECS)))
+        .timeout(timeout)
+        .tls_info(true);
+    builder = match config.tls_backend {
+        TlsBackend::Native => builder.use_native_tls(),
+        TlsBackend::Rustls => builder.use_rustls_tls(),
+    };
+    if let Some(max_idle) = pool.max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(max_idle);
+    }
+    if let Some(secs) = pool.idle_timeout_secs {
+        builder = builder.pool_idle_timeout(Duration::from_secs(secs));
+    }
+    for cert in certs {
+        builder = builder.add_root_certificate(cert.clone());
+    }
+    builder.build()
+}
+
+/// 在 debug 日志中记录上游响应的 TLS 信息，供审计使用
+///
+/// reqwest 的 `TlsInfo` 只提供对端证书，两种 TLS 实现都无法取得实际协商的版本，日志中如实注明；
+/// 低于 `outbound.min_tls_version` 的连接在握手时就会失败
+pub fn log_tls(response: &reqwest::Response) {
+    if response.extensions().get::<tls::TlsInfo>().is_some() {
+        debug!("上游响应: url={}, tls_version=unknown (not reported by the TLS backend, at least outbound.min_tls_version)", response.url());
+    } else {
+        debug!("上游响应: url={}, tls=none", response.url());
+    }
+}
@@ -0,0 +1,264 @@
+use super::{OAuthProvider, OAuthProviderType, ProviderCapabilities, TokenGrant, TokenResponse, UnifiedUserInfo, YggdrasilProfile};
+use super::error::{ProviderError, check_token_response, read_json};
+use crate::config::OAuthProviderConfig;
+use async_trait::async_trait;
+use serde::Deserialize;
+use tracing::debug;
+
+use anyhow::Result;
+
+/// 未配置租户时使用的租户
+const DEFAULT_TENANT: &str = "common";
+
+const XBL_AUTHENTICATE_URL: &str = "https://user.auth.xboxlive.com/user/authenticate";
+const XSTS_AUTHORIZE_URL: &str = "https://xsts.auth.xboxlive.com/xsts/authorize";
+const MINECRAFT_LOGIN_URL: &str = "https://api.minecraftservices.com/authentication/login_with_xbox";
+const MINECRAFT_PROFILE_URL: &str = "https://api.minecraftservices.com/minecraft/profile";
+
+/// Xbox Live / XSTS 认证响应
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct XboxAuthResponse {
+    token: String,
+    display_claims: XboxDisplayClaims,
+}
+
+#[derive(Deserialize, Debug)]
+struct XboxDisplayClaims {
+    xui: Vec<XboxUserClaims>,
+}
+
+#[derive(Deserialize, Debug)]
+struct XboxUserClaims {
+    uhs: String,
+}
+
+impl XboxAuthResponse {
+    fn user_hash(&self) -> Result<&str, ProviderError> {
+        self.display_claims.xui.first()
+            .map(|claims| claims.uhs.as_str())
+            .ok_or_else(|| ProviderError::Parse("missing user hash in Xbox Live response".to_string()))
+    }
+}
+
+/// Minecraft 服务登录响应
+#[derive(Deserialize, Debug)]
+struct MinecraftLoginResponse {
+    /// 账号标识（不是玩家名）
+    username: String,
+    access_token: String,
+}
+
+/// Minecraft 玩家档案
+#[derive(Deserialize, Debug)]
+struct MinecraftProfile {
+    id: String,
+    name: String,
+}
+
+pub struct MicrosoftProvider {
+    config: OAuthProviderConfig,
+    name: String,
+    client: reqwest::Client,
+}
+
+impl MicrosoftProvider {
+    pub fn new(config: OAuthProviderConfig, name: String, client: reqwest::Client) -> Self {
+        Self { config, name, client }
+    }
+
+    fn tenant(&self) -> &str {
+        self.config.tenant.as_deref().unwrap_or(DEFAULT_TENANT)
+    }
+
+    /// 获取 v2.0 端点地址，如 `{tenant}/oauth2/v2.0/token`
+    fn endpoint(&self, path: &str) -> Result<url::Url, url::ParseError> {
+        self.config.provider_type.endpoint(&format!("{}/oauth2/v2.0/{}", self.tenant(), path))
+    }
+
+    /// 依次完成 Xbox Live 与 XSTS 认证，返回 XSTS 令牌与用户哈希
+    async fn authenticate_xbox(&self, access_token: &str) -> Result<(String, String), ProviderError> {
+        let limit = self.config.max_response_bytes;
+
+        let response = self.client
+            .post(XBL_AUTHENTICATE_URL)
+            .json(&serde_json::json!({
+                "Properties": {
+                    "AuthMethod": "RPS",
+                    "SiteName": "user.auth.xboxlive.com",
+                    "RpsTicket": format!("d={}", access_token),
+                },
+                "RelyingParty": "http://auth.xboxlive.com",
+                "TokenType": "JWT",
+            }))
+            .send().await?
+            .error_for_status()?;
+        let xbl: XboxAuthResponse = read_json(response, limit).await?;
+
+        let response = self.client
+            .post(XSTS_AUTHORIZE_URL)
+            .json(&serde_json::json!({
+                "Properties": {
+                    "SandboxId": "RETAIL",
+                    "UserTokens": [xbl.token],
+                },
+                "RelyingParty": "rp://api.minecraftservices.com/",
+                "TokenType": "JWT",
+            }))
+            .send().await?
+            .error_for_status()?;
+        let xsts: XboxAuthResponse = read_json(response, limit).await?;
+        let user_hash = xsts.user_hash()?.to_string();
+
+        Ok((xsts.token, user_hash))
+    }
+
+    async fn request_token(&self, form: &[(&str, &str)]) -> Result<TokenGrant, ProviderError> {
+        let response = self.client
+            .post(self.endpoint("token")?)
+            .form(form)
+            .send().await?;
+        let limit = self.config.max_response_bytes;
+        let token_data: TokenResponse = read_json(check_token_response(response, limit).await?, limit).await?;
+        token_data.check_token_type()?;
+
+        let mut grant = token_data.into_grant();
+        // `offline_access` 不会出现在返回的 scope 中，是否授予只能看有没有刷新令牌
+        if grant.refresh_token.is_some()
+            && let Some(granted) = &mut grant.granted_scopes
+        {
+            granted.push("offline_access".to_string());
+        }
+        Ok(grant)
+    }
+}
+
+#[async_trait]
+impl OAuthProvider for MicrosoftProvider {
+    fn get_authorize_url(&self, redirect_uri: &str, state: &str) -> Result<String> {
+        let mut url = self.endpoint("authorize")?;
+        url.query_pairs_mut()
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", redirect_uri)
+            .append_pair("response_type", "code")
+            .append_pair("response_mode", "query")
+            .append_pair("state", state)
+            .append_pair("scope", &self.config.scopes.join(" "));
+
+        Ok(url.into())
+    }
+
+    /// 未配置 `end_session_url` 时使用 v2.0 的 logout 端点
+    fn end_session_url(&self, post_logout_redirect: &str) -> Option<String> {
+        let mut url = match &self.config.end_session_url {
+            Some(url) => url::Url::parse(url).ok()?,
+            None => self.endpoint("logout").ok()?,
+        };
+        url.query_pairs_mut().append_pair("post_logout_redirect_uri", post_logout_redirect);
+        Some(url.into())
+    }
+
+    fn supports_pkce(&self) -> bool {
+        self.config.pkce.unwrap_or(true)
+    }
+
+    async fn exchange_token(
+        &self,
+        code: &str,
+        redirect_uri: &str,
+        code_verifier: Option<&str>,
+    ) -> Result<TokenGrant, ProviderError> {
+        let scopes = self.config.scopes.join(" ");
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("client_id", &self.config.client_id),
+            ("client_secret", &self.config.client_secret),
+            ("redirect_uri", redirect_uri),
+            ("code", code),
+            ("scope", &scopes),
+        ];
+        if let Some(code_verifier) = code_verifier {
+            form.push(("code_verifier", code_verifier));
+        }
+        let token = self.request_token(&form).await?;
+
+        debug!("Microsoft Token 获取成功");
+        Ok(token)
+    }
+
+    async fn refresh_token(&self, refresh_token: &str) -> Result<TokenGrant, ProviderError> {
+        let scopes = self.config.scopes.join(" ");
+        let token = self.request_token(&[
+            ("grant_type", "refresh_token"),
+            ("client_id", &self.config.client_id),
+            ("client_secret", &self.config.client_secret),
+            ("refresh_token", refresh_token),
+            ("scope", &scopes),
+        ]).await?;
+
+        debug!("Microsoft Token 刷新成功");
+        Ok(token)
+    }
+
+    /// 经 Xbox Live → XSTS → Minecraft 服务获取用户信息
+    ///
+    /// 访问令牌需要包含 `XboxLive.signin` 权限；账号没有购买 Minecraft 时角色列表为空
+    async fn get_user_info(&self, access_token: &str) -> Result<UnifiedUserInfo, ProviderError> {
+        let limit = self.config.max_response_bytes;
+        let (xsts_token, user_hash) = self.authenticate_xbox(access_token).await?;
+
+        let response = self.client
+            .post(MINECRAFT_LOGIN_URL)
+            .json(&serde_json::json!({
+                "identityToken": format!("XBL3.0 x={};{}", user_hash, xsts_token),
+            }))
+            .send().await?
+            .error_for_status()?;
+        let login: MinecraftLoginResponse = read_json(response, limit).await?;
+
+        let response = self.client
+            .get(MINECRAFT_PROFILE_URL)
+            .bearer_auth(&login.access_token)
+            .send().await?;
+        // 未购买游戏的账号没有玩家档案
+        let profiles = if response.status() == reqwest::StatusCode::NOT_FOUND {
+            Vec::new()
+        } else {
+            let profile: MinecraftProfile = read_json(response.error_for_status()?, limit).await?;
+            vec![YggdrasilProfile { id: profile.id, name: profile.name, properties: Vec::new() }]
+        };
+
+        debug!("Microsoft 用户信息获取成功: account={}, profiles={}", login.username, profiles.len());
+
+        let nickname = profiles.first()
+            .map(|profile| profile.name.clone())
+            .unwrap_or_else(|| login.username.clone());
+        let primary_profile = UnifiedUserInfo::select_primary_profile(&profiles, &self.config.primary_profile, |_| None);
+        Ok(UnifiedUserInfo {
+            subject: UnifiedUserInfo::make_subject(&self.name, &login.username),
+            uid: login.username,
+            nickname,
+            // Xbox Live 不提供邮箱
+            email: String::new(),
+            provider: self.name.clone(),
+            provider_type: self.provider_type(),
+            profiles,
+            avatar_url: None,
+            last_modified: None,
+            primary_profile,
+        })
+    }
+
+    fn provider_type(&self) -> OAuthProviderType {
+        self.config.provider_type.clone()
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            refresh_token: true,
+            pkce: self.supports_pkce(),
+            profiles: true,
+            ..Default::default()
+        }
+    }
+}
@@ -1,19 +1,31 @@
 pub mod blessingskin;
+pub mod error;
+pub mod microsoft;
+pub mod flow;
+pub mod health;
+pub mod http;
+pub mod preflight;
+pub mod session;
 
 use axum::{
-    extract::{Path, Query, State, FromRequestParts, Request},
-    http::{StatusCode, request::Parts},
-    response::{IntoResponse, Redirect, Response},
+    body::{Body, Bytes},
+    extract::{ConnectInfo, Form, Path, Query, State, FromRequestParts, Request},
+    http::{HeaderMap, StatusCode, header, request::Parts},
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{MethodRouter, get},
     Json,
     middleware::Next,
 };
-use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
-use jwt::{SignWithKey, VerifyWithKey};
+use axum_extra::extract::cookie::CookieJar;
+use futures::{StreamExt, stream};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use url::Url;
 use uuid::Uuid;
-use std::{fmt, str::FromStr, sync::Arc, time::{Duration, SystemTime}};
-use crate::AppState;
-use tracing::{info, debug};
+use std::{collections::HashMap, fmt, net::{IpAddr, SocketAddr}, str::FromStr, sync::Arc, time::{Duration, Instant, SystemTime}};
+use crate::{AppState, cookies, csrf, error::ApiError, config::{AvatarConfig, CallbackSuccessMode, OAuthProviderConfig, PrimaryProfileRule, ProviderVisibility, is_valid_provider_name}};
+use self::error::ProviderError;
+use tracing::{info, debug, warn};
 use async_trait::async_trait;
 
 use anyhow::Result;
@@ -25,6 +37,130 @@ use anyhow::Result;
 pub struct AuthRequest {
     pub code: String,
     pub state: String,
+    /// 提供者附带的其它查询参数
+    #[serde(flatten)]
+    pub extra: HashMap<String, String>,
+}
+
+/// OAuth2 令牌端点的响应（RFC 6749 5.1）
+#[derive(Deserialize, Debug)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    #[serde(default)]
+    pub expires_in: u64,
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+    /// 实际授予的权限（以空格分隔），与申请的相同时上游可以省略
+    #[serde(default)]
+    pub scope: Option<String>,
+}
+
+/// 令牌端点换得的令牌
+#[derive(Debug)]
+pub struct TokenGrant {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    pub expires_in: Duration,
+    /// 上游返回的实际授予权限，未返回时视为全部授予
+    pub granted_scopes: Option<Vec<String>>,
+}
+
+impl TokenResponse {
+    pub fn into_grant(self) -> TokenGrant {
+        TokenGrant {
+            access_token: self.access_token,
+            refresh_token: self.refresh_token,
+            expires_in: Duration::from_secs(self.expires_in),
+            granted_scopes: self.scope
+                .map(|scope| scope.split_whitespace().map(String::from).collect()),
+        }
+    }
+
+
+    /// 只支持 Bearer 令牌，其它类型（如 `mac`）无法用于后续的 `bearer_auth` 请求
+    pub fn check_token_type(&self) -> Result<(), ProviderError> {
+        if self.token_type.eq_ignore_ascii_case("bearer") {
+            Ok(())
+        } else {
+            Err(ProviderError::UnsupportedTokenType(self.token_type.clone()))
+        }
+    }
+}
+
+/// 令牌内省请求（RFC 7662）
+#[derive(Deserialize)]
+pub struct IntrospectRequest {
+    pub token: String,
+}
+
+/// 令牌内省时携带共享密钥的请求头
+pub const INTROSPECTION_SECRET_HEADER: &str = "X-Introspection-Secret";
+
+/// 登录请求的查询参数
+#[derive(Deserialize)]
+pub struct LoginQuery {
+    /// 覆盖默认申请的权限（以空格或逗号分隔）
+    pub scopes: Option<String>,
+    /// 覆盖回调成功后的行为
+    pub mode: Option<CallbackSuccessMode>,
+    /// 登录成功后返回的页面（同源的相对路径），替代默认的首页
+    pub redirect: Option<String>,
+}
+
+/// `redirect` 参数的最大长度
+const MAX_REDIRECT_LEN: usize = 2048;
+/// 登录与回调限流的统计窗口
+const LOGIN_RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// 登录或回调的限流 key，按提供者与 IP 分别计数
+fn login_rate_key(kind: &str, provider_name: &str, ip: IpAddr) -> String {
+    format!("{}:{}:{}", kind, provider_name, ip)
+}
+
+/// 按提供者与 IP 限制登录或回调的频率，`limit` 未设置时不限制
+///
+/// 一个提供者上的请求不会占用其它提供者的额度
+async fn check_login_rate(state: &AppState, kind: &str, provider_name: &str, ip: IpAddr, limit: Option<u32>) -> Result<(), ApiError> {
+    let Some(limit) = limit else {
+        return Ok(());
+    };
+    state.rate_limiter.try_record(&login_rate_key(kind, provider_name, ip), 1, limit.into(), LOGIN_RATE_WINDOW).await
+        .map_err(|after| {
+            ApiError::new(StatusCode::TOO_MANY_REQUESTS, "Too many login attempts, please try again later")
+                .with_code("login_rate_limited")
+                .with_retry_after(after)
+        })
+}
+
+/// 签名后作为 OAuth2 state 传递的登录流程信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LoginState {
+    nonce: Uuid,
+    /// 发起登录的提供者，回调必须来自同一个提供者
+    provider: String,
+    /// 签发时间，超过 `state_ttl_secs` 的 state 会被拒绝
+    issued_at: SystemTime,
+    /// 本次流程申请的权限
+    scopes: Vec<String>,
+    /// 本次流程指定的回调成功行为
+    #[serde(default)]
+    mode: Option<CallbackSuccessMode>,
+    /// 登录成功后返回的页面（已校验为同源的相对路径）
+    #[serde(default)]
+    redirect: Option<String>,
+}
+
+/// 是否为同源的相对路径，用于防止开放重定向
+///
+/// 只接受以单个 `/` 开头的路径；`//host` 与 `/\host` 会被浏览器当作其它站点，
+/// 控制字符可能被截断或改写，一并拒绝
+fn is_safe_redirect(path: &str) -> bool {
+    path.len() <= MAX_REDIRECT_LEN
+        && path.starts_with('/')
+        && !path.starts_with("//")
+        && !path.contains('\\')
+        && !path.chars().any(char::is_control)
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -41,22 +177,113 @@ pub struct YggdrasilProfile {
     pub properties: Vec<YggdrasilKVPair>,
 }
 
+impl YggdrasilProfile {
+    /// 合并额外属性，同名属性以传入的值为准
+    pub fn merge_properties(&mut self, extra: &HashMap<String, String>) {
+        for (name, value) in extra {
+            match self.properties.iter_mut().find(|p| &p.name == name) {
+                Some(property) => property.value = value.clone(),
+                None => self.properties.push(YggdrasilKVPair { name: name.clone(), value: value.clone() }),
+            }
+        }
+    }
+}
+
 /// 统一的用户信息结构
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UnifiedUserInfo {
-    pub uid: String,           // 统一使用字符串 ID
+    pub subject: String,       // 跨提供者唯一的身份标识 `{provider}:{uid}`
+    pub uid: String,           // 统一使用字符串 ID（提供者内部 ID）
     pub nickname: String,
     pub email: String,
     pub provider: String,       // 提供者名称
     pub provider_type: OAuthProviderType,  // 提供者类型
     #[serde(default)]
     pub profiles: Vec<YggdrasilProfile>,  // 玩家角色列表
+    #[serde(default)]
+    pub avatar_url: Option<String>,       // 头像地址（提供者未提供时为 None）
+    #[serde(default)]
+    pub last_modified: Option<u64>,       // 角色数据的最近修改时间（Unix 时间戳，秒）
+    #[serde(default)]
+    pub primary_profile: Option<String>,  // 主角色名称（按 `primary_profile` 规则选出）
+}
+
+impl UnifiedUserInfo {
+    /// 生成跨提供者唯一的身份标识
+    ///
+    /// 不同提供者的 uid 可能相同，存储命名空间、配额、会话记录等
+    /// 都应使用 subject 作为身份键
+    pub fn make_subject(provider: &str, uid: &str) -> String {
+        format!("{}:{}", provider, uid)
+    }
+
+    /// 按规则选出主角色
+    ///
+    /// `modified_at` 返回角色的修改时间，提供者不提供时返回 None
+    pub fn select_primary_profile(
+        profiles: &[YggdrasilProfile],
+        rule: &PrimaryProfileRule,
+        modified_at: impl Fn(&YggdrasilProfile) -> Option<u64>,
+    ) -> Option<String> {
+        let selected = match rule {
+            PrimaryProfileRule::First => None,
+            PrimaryProfileRule::MostRecent => profiles.iter()
+                .filter_map(|profile| modified_at(profile).map(|time| (time, profile)))
+                .max_by_key(|(time, _)| *time)
+                .map(|(_, profile)| profile),
+            PrimaryProfileRule::Name(name) => profiles.iter().find(|profile| &profile.name == name),
+        };
+        selected.or(profiles.first()).map(|profile| profile.name.clone())
+    }
+
+    /// 提供者未提供头像时，按配置补全头像地址
+    ///
+    /// 配置了角色头像模板且有主角色时使用主角色生成地址；
+    /// 否则开启 Gravatar 时根据邮箱哈希生成地址（以默认头像作为 Gravatar 的兜底），
+    /// 再否则使用配置的默认头像
+    pub fn resolve_avatar(&mut self, config: &AvatarConfig) {
+        if self.avatar_url.is_some() {
+            return;
+        }
+        let primary = self.primary_profile.as_ref()
+            .and_then(|name| self.profiles.iter().find(|profile| &profile.name == name));
+        self.avatar_url = if let (Some(template), Some(profile)) = (&config.profile_template, primary) {
+            Some(template.replace("{id}", &profile.id).replace("{name}", &profile.name))
+        } else if config.gravatar && !self.email.is_empty() {
+            Some(gravatar_url(&self.email, config.default_url.as_deref()))
+        } else {
+            config.default_url.clone()
+        };
+    }
+}
+
+/// 根据邮箱生成 Gravatar 头像地址（使用 SHA-256 哈希）
+fn gravatar_url(email: &str, default_url: Option<&str>) -> String {
+    let digest = Sha256::digest(email.trim().to_lowercase().as_bytes());
+    let hash: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    Url::parse_with_params(
+        &format!("https://www.gravatar.com/avatar/{}", hash),
+        &[("d", default_url.unwrap_or("identicon"))],
+    )
+    .map(String::from)
+    .expect("Gravatar URL is always valid")
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct TokenInformation {
+    /// 会话 ID，刷新时保持不变
+    #[serde(default)]
+    pub session_id: Uuid,
+    /// 用户最近一次完成 OAuth 认证的时间（Unix 时间戳，秒），刷新时保持不变
+    #[serde(default)]
+    pub auth_time: u64,
     pub access_token: String,
+    #[serde(default)]
+    pub refresh_token: Option<String>,
     pub provider_name: String,
+    /// 登录时申请并获得的权限
+    #[serde(default)]
+    pub scopes: Vec<String>,
     pub expire_date: SystemTime,
     pub user_info: UnifiedUserInfo
 }
@@ -65,7 +292,7 @@ impl<S> FromRequestParts<S> for UnifiedUserInfo
 where
     S: Send + Sync,
 {
-    type Rejection = Response;
+    type Rejection = ApiError;
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
         // 从 extensions 中提取用户信息（由 auth_middleware 插入）
@@ -74,7 +301,191 @@ where
             .get::<UnifiedUserInfo>()
             .cloned()
             .ok_or_else(|| {
-                (StatusCode::UNAUTHORIZED, "Authentication required").into_response()
+                ApiError::unauthorized("Authentication required")
+            })
+    }
+}
+
+/// 已认证用户的提取器
+///
+/// 通过 `Deref` 直接访问 `uid`、`nickname`、`email` 等字段；
+/// 只读取 auth_middleware 注入的用户信息，公开接口请使用 [`OptionalAuthUser`]
+#[derive(Debug, Clone)]
+pub struct AuthUser(pub UnifiedUserInfo);
+
+impl AuthUser {
+    /// 用户拥有的游戏角色
+    pub fn players(&self) -> &[YggdrasilProfile] {
+        &self.0.profiles
+    }
+}
+
+impl std::ops::Deref for AuthUser {
+    type Target = UnifiedUserInfo;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<S> FromRequestParts<S> for AuthUser
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        UnifiedUserInfo::from_request_parts(parts, state).await.map(Self)
+    }
+}
+
+impl<S> axum::extract::OptionalFromRequestParts<S> for AuthUser
+where
+    S: Send + Sync,
+{
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Option<Self>, Self::Rejection> {
+        Ok(parts.extensions.get::<UnifiedUserInfo>().cloned().map(Self))
+    }
+}
+
+/// 可选认证的提取器，用于同时面向游客与登录用户的公开接口
+///
+/// 不需要经过 auth_middleware：请求携带会话 token（`Authorization` 请求头或 cookie）时
+/// 仍会校验签名、单会话与过期时间，任何一项不通过都返回 `None` 而不是 401。
+/// 过期会话不会在这里续期，也不会清除 cookie
+#[derive(Debug, Clone)]
+pub struct OptionalAuthUser(pub Option<UnifiedUserInfo>);
+
+impl FromRequestParts<Arc<AppState>> for OptionalAuthUser {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        // 位于 auth_middleware 之后时直接使用已校验的用户信息
+        if let Some(user) = parts.extensions.get::<UnifiedUserInfo>() {
+            return Ok(Self(Some(user.clone())));
+        }
+
+        Ok(Self(active_session(state, &parts.headers).map(|claims| claims.user_info)))
+    }
+}
+
+/// 读取并校验请求携带的会话（`Authorization` 请求头优先，其次是 cookie）
+///
+/// 签名、单会话与过期时间任何一项不通过都返回 `None`；不会续期，也不会清除 cookie
+fn active_session(state: &AppState, headers: &HeaderMap) -> Option<TokenInformation> {
+    let token_value = bearer_token(headers)
+        .map(str::to_string)
+        .or_else(|| cookies::session_token(&CookieJar::from_headers(headers)))?;
+    let token_claims: TokenInformation = state.signer().verify(&token_value).ok()?;
+    let superseded = state.config().oauth.single_session_per_user
+        && state.sessions.is_superseded(&token_claims.user_info.subject, &token_claims.session_id);
+    (!superseded && !is_revoked(state, &token_claims) && SystemTime::now() <= token_claims.expire_date)
+        .then_some(token_claims)
+}
+
+/// 吊销请求所携带会话的上游令牌（删除账号时使用）
+///
+/// 提供者不支持吊销时直接返回；先吊销刷新令牌，使其无法再换取新的访问令牌
+pub async fn revoke_upstream_tokens(state: &AppState, headers: &HeaderMap) -> Result<(), ProviderError> {
+    let Some(claims) = active_session(state, headers) else {
+        return Ok(());
+    };
+    let provider_config = state.get_provider(&claims.provider_name)
+        .ok_or_else(|| ProviderError::Config(format!("provider {} no longer configured", claims.provider_name)))?;
+    let provider = create_oauth_provider(&provider_config, &claims.provider_name, &state.http_client(&claims.provider_name))?;
+    if !provider.capabilities().revocation {
+        return Ok(());
+    }
+    if let Some(refresh_token) = &claims.refresh_token {
+        provider.revoke_token(refresh_token).await?;
+    }
+    provider.revoke_token(&claims.access_token).await
+}
+
+/// 会话是否已在服务端被吊销（登出或“登出所有会话”），或已超过 `max_session_lifetime_secs`
+///
+/// 限制最长存续时间后，“登出所有会话”的记录只需要保留这么久
+fn is_revoked(state: &AppState, claims: &TokenInformation) -> bool {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    now.saturating_sub(claims.auth_time) > state.config().oauth.max_session_lifetime_secs
+        || state.sessions.is_revoked(&claims.user_info.subject, &claims.session_id, claims.auth_time)
+}
+
+/// 单个会话的吊销记录需要保留到的时间
+///
+/// 刷新会沿用会话 ID 并推后过期时间，被出示的 cookie 过期后，同一会话的其它副本仍可能有效。
+/// 会话最长只能存续到 `auth_time + max_session_lifetime_secs`，之后由 `is_revoked` 直接拒绝
+fn revocation_deadline(claims: &TokenInformation, max_lifetime: Duration) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(claims.auth_time) + max_lifetime
+}
+
+/// 经过校验的 `{provider}` 路径参数
+///
+/// 在查找配置之前拒绝过长或包含非法字符的名称，避免把任意输入写进日志
+pub struct ValidatedProvider(pub String);
+
+impl FromRequestParts<Arc<AppState>> for ValidatedProvider {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let Path(name) = Path::<String>::from_request_parts(parts, state).await
+            .map_err(|e| ApiError::bad_request(e.body_text()))?;
+        if !is_valid_provider_name(&name, state.config().oauth.max_provider_name_len) {
+            return Err(ApiError::bad_request("Malformed provider name").with_code("invalid_provider_name"));
+        }
+        Ok(Self(name))
+    }
+}
+
+/// 当前会话信息（由 auth_middleware 注入 extensions）
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionInfo {
+    pub provider: String,
+    /// 会话获得的权限
+    pub scopes: Vec<String>,
+    /// 最近一次认证时间（Unix 时间戳，秒）
+    pub auth_time: u64,
+    /// 会话过期时间（Unix 时间戳，秒）
+    pub expires_at: u64,
+}
+
+impl SessionInfo {
+    /// 要求最近一次认证在 `max_age` 之内
+    ///
+    /// 否则返回 401 `step_up_required`，前端应重新走一遍 OAuth 登录流程
+    pub fn require_fresh_auth(&self, max_age: Duration) -> Result<(), ApiError> {
+        let auth_time = SystemTime::UNIX_EPOCH + Duration::from_secs(self.auth_time);
+        let fresh = SystemTime::now()
+            .duration_since(auth_time)
+            .is_ok_and(|age| age <= max_age);
+        if fresh {
+            return Ok(());
+        }
+        Err(ApiError::unauthorized("Recent authentication required")
+            .with_code("step_up_required")
+            .with_field("max_age", max_age.as_secs())
+            .with_field("login_url", format!("/api/oauth/{}/login", self.provider)))
+    }
+}
+
+impl<S> FromRequestParts<S> for SessionInfo
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<SessionInfo>()
+            .cloned()
+            .ok_or_else(|| {
+                ApiError::unauthorized("Authentication required")
             })
     }
 }
@@ -100,12 +511,33 @@ impl OAuthProviderType {
         }
     }
 
+    /// 配置中未指定 scopes 时申请的默认权限
+    pub fn default_scopes(&self) -> Vec<String> {
+        let scopes: &[&str] = match self {
+            Self::BlessingSkin(_) => &["User.Read", "Player.Read"],
+            Self::Microsoft => &["XboxLive.signin", "offline_access"],
+        };
+        scopes.iter().map(|scope| scope.to_string()).collect()
+    }
+
     pub fn base_url(&self) -> &str {
         match self {
             Self::BlessingSkin(url) => url,
             Self::Microsoft => "https://login.microsoftonline.com",
         }
     }
+
+    /// 将子路径拼接到 base URL 之后
+    ///
+    /// base URL 可以包含路径前缀（如 `https://host/skin`），末尾有无斜杠均可
+    pub fn endpoint(&self, path: &str) -> Result<Url, url::ParseError> {
+        let mut base = Url::parse(self.base_url())?;
+        if !base.path().ends_with('/') {
+            let path_with_slash = format!("{}/", base.path());
+            base.set_path(&path_with_slash);
+        }
+        base.join(path.trim_start_matches('/'))
+    }
 }
 
 impl fmt::Display for OAuthProviderType {
@@ -153,22 +585,84 @@ impl<'a> Deserialize<'a> for OAuthProviderType {
     }
 }
 
+/// OAuth 提供者能力描述
+///
+/// 前端可以据此决定是否展示“刷新会话”“关联角色”等功能
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct ProviderCapabilities {
+    /// 是否支持刷新令牌
+    pub refresh_token: bool,
+    /// 是否支持 PKCE
+    pub pkce: bool,
+    /// 是否能获取 Yggdrasil 角色列表
+    pub profiles: bool,
+    /// 是否支持吊销上游令牌
+    pub revocation: bool,
+}
+
 /// OAuth 提供者接口
 /// 
 /// 每个 OAuth 提供者都需要实现这个 trait
 #[async_trait]
 pub trait OAuthProvider: Send + Sync {
     /// 获取授权 URL
-    fn get_authorize_url(&self, redirect_uri: &str, state: &str) -> String;
+    fn get_authorize_url(&self, redirect_uri: &str, state: &str) -> Result<String>;
+
+    /// 是否在授权流程中使用 PKCE
+    fn supports_pkce(&self) -> bool {
+        false
+    }
+
+    /// 获取附带 PKCE（S256）参数的授权 URL
+    fn get_authorize_url_with_pkce(&self, redirect_uri: &str, state: &str, code_challenge: &str) -> Result<String> {
+        let mut url = Url::parse(&self.get_authorize_url(redirect_uri, state)?)?;
+        url.query_pairs_mut()
+            .append_pair("code_challenge", code_challenge)
+            .append_pair("code_challenge_method", "S256");
+        Ok(url.into())
+    }
     
     /// 使用授权码交换访问令牌
-    async fn exchange_token(&self, code: &str, redirect_uri: &str) -> Result<(String, Duration)>;
+    ///
+    /// 返回访问令牌、刷新令牌（如有）、有效期与实际授予的权限。只支持 Bearer 令牌，
+    /// 上游返回其它类型时应返回 `ProviderError::UnsupportedTokenType`。
+    /// 授权时使用了 PKCE 的需要传入对应的 `code_verifier`
+    async fn exchange_token(
+        &self,
+        code: &str,
+        redirect_uri: &str,
+        code_verifier: Option<&str>,
+    ) -> Result<TokenGrant, ProviderError>;
+
+    /// 使用刷新令牌换取新的访问令牌
+    ///
+    /// 不支持刷新的提供者保持默认实现，调用方应视为需要重新登录
+    async fn refresh_token(&self, _refresh_token: &str) -> Result<TokenGrant, ProviderError> {
+        Err(ProviderError::Unsupported("token refresh".to_string()))
+    }
     
+    /// 吊销上游的访问令牌或刷新令牌（RFC 7009）
+    ///
+    /// 只有 `capabilities().revocation` 为 true 的提供者才会被调用
+    async fn revoke_token(&self, _token: &str) -> Result<(), ProviderError> {
+        Err(ProviderError::Unsupported("token revocation".to_string()))
+    }
+
     /// 获取用户信息
-    async fn get_user_info(&self, access_token: &str) -> Result<UnifiedUserInfo>;
+    async fn get_user_info(&self, access_token: &str) -> Result<UnifiedUserInfo, ProviderError>;
+    
+    /// 获取提供者的登出地址，登出后跳转回 `post_logout_redirect`
+    ///
+    /// 没有已知登出端点的提供者保持默认实现
+    fn end_session_url(&self, _post_logout_redirect: &str) -> Option<String> {
+        None
+    }
     
     /// 获取提供者类型
     fn provider_type(&self) -> OAuthProviderType;
+
+    /// 获取提供者支持的能力
+    fn capabilities(&self) -> ProviderCapabilities;
 }
 
 /// 根据配置创建 OAuth 提供者实例
@@ -177,36 +671,213 @@ pub trait OAuthProvider: Send + Sync {
 /// 
 /// * `provider_config` - OAuth 提供者配置
 /// * `provider_name` - 提供者名称
+/// * `client` - 共享的出站 HTTP 客户端
 /// 
 /// # 返回
 /// 
-/// 返回对应类型的 OAuthProvider trait 对象
+/// 返回对应类型的 OAuthProvider trait 对象；尚未实现的类型返回 `ProviderError::Unsupported`，
+/// 避免配置错误在请求处理中变成 panic
 pub fn create_oauth_provider(
-    provider_config: &crate::config::OAuthProviderConfig,
+    provider_config: &OAuthProviderConfig,
     provider_name: &str,
-) -> Box<dyn OAuthProvider> {
-    match provider_config.provider_type {
+    client: &reqwest::Client,
+) -> Result<Box<dyn OAuthProvider>, ProviderError> {
+    Ok(match provider_config.provider_type {
         OAuthProviderType::BlessingSkin(_) => Box::new(
-            blessingskin::BlessingSkinProvider::new(provider_config.clone(), provider_name.to_string())
+            blessingskin::BlessingSkinProvider::new(provider_config.clone(), provider_name.to_string(), client.clone())
         ),
-        OAuthProviderType::Microsoft => todo!()
+        OAuthProviderType::Microsoft => Box::new(
+            microsoft::MicrosoftProvider::new(provider_config.clone(), provider_name.to_string(), client.clone())
+        ),
+    })
+}
+
+/// 生成 PKCE 的 code_verifier 与对应的 S256 code_challenge
+fn generate_pkce_pair() -> (String, String) {
+    use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+
+    let verifier = URL_SAFE_NO_PAD.encode(rand::random::<[u8; 32]>());
+    let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+    (verifier, challenge)
+}
+
+/// 从会话过期时间中减去一个随机抖动，避免大量会话同时过期
+///
+/// 抖动只会缩短有效期，因此会话永远不会比上游令牌活得更久
+fn apply_expiry_jitter(expire_duration: Duration, max_jitter_secs: Option<u64>) -> Duration {
+    let max_jitter = match max_jitter_secs {
+        Some(secs) if secs > 0 => secs.min(expire_duration.as_secs()),
+        _ => return expire_duration,
+    };
+    let jitter = rand::random_range(0..=max_jitter);
+    expire_duration.saturating_sub(Duration::from_secs(jitter))
+}
+
+/// 检查请求来源是否允许使用该提供者
+///
+/// internal 提供者只允许受信任地址段访问
+fn check_visibility(
+    state: &AppState,
+    provider_name: &str,
+    provider_config: &OAuthProviderConfig,
+    addr: SocketAddr,
+) -> Result<(), ApiError> {
+    if provider_config.visibility == ProviderVisibility::Internal && !state.is_trusted_ip(addr.ip()) {
+        return Err(ApiError::forbidden(format!("Provider {} is internal-only", provider_name)).with_code("provider_internal"));
+    }
+    Ok(())
+}
+
+/// 签名会话并计算 cookie 的过期时间
+///
+/// cookie 会比会话多保留一个刷新宽限期，否则浏览器会在会话过期时直接丢弃 cookie，
+/// auth_middleware 就没有机会使用刷新令牌续期
+fn sign_session(state: &AppState, claims: &TokenInformation) -> Result<(String, time::OffsetDateTime), jwt::Error> {
+    let token = state.signer().sign(claims)?;
+    let grace = Duration::from_secs(state.config().oauth.refresh_grace_period_secs);
+    let expires = time::OffsetDateTime::from(claims.expire_date + grace);
+    Ok((token, expires))
+}
+
+/// 使用刷新令牌为已过期或即将过期的会话续期
+async fn refresh_session(state: &AppState, claims: &TokenInformation) -> Result<TokenInformation, ProviderError> {
+    let refresh_token = claims.refresh_token.as_deref()
+        .ok_or_else(|| ProviderError::Unsupported("token refresh without refresh token".to_string()))?;
+    let mut provider_config = state.get_provider(&claims.provider_name)
+        .ok_or_else(|| ProviderError::Config(format!("provider {} no longer configured", claims.provider_name)))?;
+    // 刷新时沿用登录时申请的权限
+    if !claims.scopes.is_empty() {
+        provider_config.scopes = claims.scopes.clone();
+    }
+    let provider = create_oauth_provider(&provider_config, &claims.provider_name, &state.http_client(&claims.provider_name))?;
+
+    let grant = provider.refresh_token(refresh_token).await?;
+    let expire_duration = apply_expiry_jitter(grant.expires_in, state.config().oauth.expiry_jitter_secs);
+    debug!("会话已刷新: subject={}, expires in {}s", claims.user_info.subject, expire_duration.as_secs());
+
+    Ok(TokenInformation {
+        session_id: claims.session_id,
+        auth_time: claims.auth_time,
+        // 提供者未轮换刷新令牌时继续使用旧的
+        refresh_token: grant.refresh_token.or_else(|| claims.refresh_token.clone()),
+        access_token: grant.access_token,
+        provider_name: claims.provider_name.clone(),
+        scopes: retain_granted(&claims.scopes, grant.granted_scopes.as_deref()),
+        expire_date: SystemTime::now() + expire_duration,
+        user_info: claims.user_info.clone(),
+    })
+}
+
+/// 申请的权限中实际被授予的部分，上游未返回授予的权限时视为全部授予
+fn retain_granted(requested: &[String], granted: Option<&[String]>) -> Vec<String> {
+    match granted {
+        Some(granted) => requested.iter()
+            .filter(|scope| granted.contains(scope))
+            .cloned()
+            .collect(),
+        None => requested.to_vec(),
+    }
+}
+
+/// 交换授权码，连接失败时按提供者配置退避重试
+///
+/// 授权码只能使用一次：只有请求尚未发出的连接错误才会重试。
+/// 请求到达上游之后的失败（超时、响应中断、OAuth 错误）都不重试，
+/// 因为上游可能已经消费了授权码，重试只会得到 `invalid_grant`
+async fn exchange_token_with_retry(
+    provider: &dyn OAuthProvider,
+    provider_config: &OAuthProviderConfig,
+    code: &str,
+    redirect_uri: &str,
+    code_verifier: Option<&str>,
+) -> Result<TokenGrant, ProviderError> {
+    let mut attempt = 0;
+    loop {
+        match provider.exchange_token(code, redirect_uri, code_verifier).await {
+            Err(e) if e.is_retryable() && attempt < provider_config.token_exchange_retries => {
+                let backoff = provider_config.token_exchange_backoff_ms.saturating_mul(1 << attempt.min(16));
+                warn!("交换授权码失败，{}ms 后重试 ({}/{}): {}", backoff, attempt + 1, provider_config.token_exchange_retries, e);
+                tokio::time::sleep(Duration::from_millis(backoff)).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
     }
 }
 
+/// 认证失败时把响应时间补齐到配置的下限（外加少量随机抖动）
+///
+/// 使“没有 cookie”“签名无效”“已过期”等失败原因无法通过响应时间区分
+async fn pad_auth_failure(state: &AppState, started: Instant) {
+    let Some(floor_ms) = state.config().oauth.auth_failure_delay_ms else {
+        return;
+    };
+    let jitter = rand::random_range(0..=floor_ms / 10);
+    let target = Duration::from_millis(floor_ms + jitter);
+    if let Some(remaining) = target.checked_sub(started.elapsed()) {
+        tokio::time::sleep(remaining).await;
+    }
+}
+
+/// 将提供者错误转换为 HTTP 响应，并把上游故障计入健康统计
+fn provider_failure(state: &AppState, provider_name: &str, error: ProviderError) -> ApiError {
+    if error.is_upstream_fault() {
+        state.provider_health.record_failure(provider_name);
+    }
+    state.events.record("login_failed", Some(provider_name), None, Some(error.to_string()));
+    error.into()
+}
+
+/// 内置的登录成功页面
+const DEFAULT_SUCCESS_PAGE: &str = r#"<!DOCTYPE html>
+<html lang="zh-CN">
+<head><meta charset="utf-8"><title>登录成功</title></head>
+<body><p>登录成功，您可以关闭此窗口了。</p></body>
+</html>
+"#;
+
 // ============= 路由处理函数 =============
 
 /// 列出所有可用的 OAuth 提供者
-pub async fn list_providers(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    let providers: Vec<_> = state
+///
+/// internal 提供者仅对受信任地址段列出
+pub async fn list_providers(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+) -> impl IntoResponse {
+    let trusted = state.is_trusted_ip(addr.ip());
+    let mut providers: Vec<_> = state
         .get_enabled_providers()
         .into_iter()
+        .filter(|(_, provider_config)| trusted || provider_config.visibility == ProviderVisibility::Public)
         .map(|(name, provider_config)| {
-            serde_json::json!({
+            let health = state.provider_health.status(&name);
+            (name, provider_config, health)
+        })
+        .collect();
+
+    // 健康的提供者排在前面，引导用户使用可用的提供者
+    if state.config().oauth.sort_by_health {
+        providers.sort_by(|(a_name, _, a_health), (b_name, _, b_health)| {
+            a_health.cmp(b_health).then_with(|| a_name.cmp(b_name))
+        });
+    }
+
+    let providers: Vec<_> = providers
+        .into_iter()
+        .filter_map(|(name, provider_config, health)| {
+            let provider = create_oauth_provider(&provider_config, &name, &state.http_client(&name))
+                .inspect_err(|e| warn!("无法创建提供者 {}: {}", name, e))
+                .ok()?;
+            Some(serde_json::json!({
                 "name": name,
                 "type": provider_config.provider_type,
                 "display_name": provider_config.provider_type.display_name(),
+                "description": provider_config.description,
+                "capabilities": provider.capabilities(),
+                "health": health,
                 "login_url": format!("/api/oauth/{}/login", name)
-            })
+            }))
         })
         .collect();
 
@@ -219,112 +890,565 @@ pub async fn list_providers(State(state): State<Arc<AppState>>) -> impl IntoResp
 /// 开始 OAuth2 登录流程（动态路由）
 pub async fn login(
     State(state): State<Arc<AppState>>,
-    Path(provider_name): Path<String>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    ValidatedProvider(provider_name): ValidatedProvider,
+    Query(query): Query<LoginQuery>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, ApiError> {
     info!("启动 {} OAuth2 登录流程", provider_name);
 
+    if let Some(rejection) = state.maintenance.reject() {
+        return Err(rejection);
+    }
+    let limit = state.get_provider(&provider_name)
+        .and_then(|provider_config| provider_config.login_per_minute)
+        .or(state.config().rate_limit.login_per_minute);
+    check_login_rate(&state, "login", &provider_name, addr.ip(), limit).await?;
+
     // 获取提供者配置
-    let provider_config = state
+    let mut provider_config = state
         .get_provider(&provider_name)
-        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Provider {} not found", provider_name)))?;
+        .ok_or_else(|| ApiError::not_found(format!("Provider {} not found", provider_name)))?;
     
     if !provider_config.enabled {
-        return Err((StatusCode::FORBIDDEN, format!("Provider {} is disabled", provider_name)));
+        return Err(ApiError::forbidden(format!("Provider {} is disabled", provider_name)).with_code("provider_disabled"));
+    }
+    check_visibility(&state, &provider_name, &provider_config, addr)?;
+
+    // 按请求覆盖本次流程申请的权限
+    if let Some(scopes) = query.scopes {
+        let scopes: Vec<String> = scopes
+            .split([' ', ','])
+            .filter(|scope| !scope.is_empty())
+            .map(str::to_string)
+            .collect();
+        if scopes.is_empty() {
+            return Err(ApiError::bad_request("No scopes requested").with_code("invalid_scope"));
+        }
+        if let Some(scope) = scopes.iter().find(|scope| !provider_config.is_scope_allowed(scope)) {
+            return Err(ApiError::bad_request(format!("Scope {} is not allowed for provider {}", scope, provider_name)).with_code("invalid_scope"));
+        }
+        provider_config.scopes = scopes;
+    }
+
+    if query.redirect.as_deref().is_some_and(|redirect| !is_safe_redirect(redirect)) {
+        return Err(ApiError::bad_request("Redirect must be a same-origin relative path").with_code("invalid_redirect"));
     }
     
     let redirect_uri = state.get_redirect_uri(&provider_name);
     
     debug!("redirect_uri: {}", redirect_uri);
     
+    // 拒绝向允许列表之外的主机发起授权
+    if !state.config().oauth.is_provider_host_allowed(&provider_config.provider_type) {
+        return Err(ApiError::internal(format!("Provider {} points to a host that is not allowed", provider_name)));
+    }
+
     // 根据提供者类型创建相应的 provider
-    let provider = create_oauth_provider(provider_config, &provider_name);
+    let provider = create_oauth_provider(&provider_config, &provider_name, &state.http_client(&provider_name))?;
     
-    let state_token = Uuid::new_v4().sign_with_key(state.secret())
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Server failed to sign JWT".to_string()))?;
-    let auth_url = provider.get_authorize_url(&redirect_uri, &state_token);
+    let nonce = Uuid::new_v4();
+    if !state.pending_flows.begin(nonce, &provider_name, addr.ip(), provider_config.max_pending_per_ip) {
+        return Err(ApiError::new(StatusCode::TOO_MANY_REQUESTS, "Too many pending logins, please try again later").with_code("too_many_pending_logins"));
+    }
+
+    let login_state = LoginState {
+        nonce,
+        provider: provider_name.clone(),
+        issued_at: SystemTime::now(),
+        scopes: provider_config.scopes.clone(),
+        mode: query.mode,
+        redirect: query.redirect,
+    };
+    let state_token = state.signer().sign(&login_state)
+        .map_err(|_| ApiError::internal("Server failed to sign JWT"))?;
+    let config = state.config();
+    let max_age = time::Duration::seconds(config.oauth.state_ttl_secs.try_into().unwrap_or(i64::MAX));
+
+    // 将 state 绑定到发起登录的浏览器，防止登录 CSRF
+    let mut jar = jar.add(cookies::oauth_state_cookie(&config.cookie, nonce.simple().to_string(), max_age));
+    let auth_url = if provider.supports_pkce() {
+        // code_verifier 不能出现在经过浏览器地址栏的 state 中，只保存在 HttpOnly cookie 里
+        let (verifier, challenge) = generate_pkce_pair();
+        jar = jar.add(cookies::pkce_verifier_cookie(&config.cookie, verifier, max_age));
+        provider.get_authorize_url_with_pkce(&redirect_uri, &state_token, &challenge)
+    } else {
+        provider.get_authorize_url(&redirect_uri, &state_token)
+    }.map_err(|e| ApiError::internal(format!("Failed to build authorize URL: {}", e)))?;
+
+    state.events.record("login_started", Some(&provider_name), None, None);
     
-    Ok(Redirect::to(&auth_url))
+    Ok((jar, Redirect::to(&auth_url)))
 }
 
 /// OAuth2 回调处理（动态路由）
 pub async fn callback(
     State(state): State<Arc<AppState>>,
-    Path(provider_name): Path<String>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    ValidatedProvider(provider_name): ValidatedProvider,
     Query(params): Query<AuthRequest>,
     jar: CookieJar,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+) -> Result<Response, ApiError> {
+    handle_callback(state, addr, provider_name, params, jar).await
+}
+
+/// 配置了 `callback_path` 的提供者使用的回调路由
+pub fn custom_callback_route(provider_name: String) -> MethodRouter<Arc<AppState>> {
+    get(move |State(state): State<Arc<AppState>>,
+              ConnectInfo(addr): ConnectInfo<SocketAddr>,
+              Query(params): Query<AuthRequest>,
+              jar: CookieJar| {
+        handle_callback(state, addr, provider_name.clone(), params, jar)
+    })
+}
+
+async fn handle_callback(
+    state: Arc<AppState>,
+    addr: SocketAddr,
+    provider_name: String,
+    params: AuthRequest,
+    jar: CookieJar,
+) -> Result<Response, ApiError> {
+    let started = Instant::now();
+    let result = process_callback(state.clone(), addr, provider_name, params, jar).await;
+    if result.is_err() {
+        pad_auth_failure(&state, started).await;
+    }
+    result
+}
+
+async fn process_callback(
+    state: Arc<AppState>,
+    addr: SocketAddr,
+    provider_name: String,
+    params: AuthRequest,
+    jar: CookieJar,
+) -> Result<Response, ApiError> {
     debug!("Received {} OAuth2 callback", provider_name);
     debug!("Authorization code: {}", params.code);
     debug!("Authorization state: {}", params.state);
 
-    let action_uuid: Uuid = params.state.verify_with_key(state.secret())
-        .map_err(|_| (StatusCode::UNAUTHORIZED, "State verification failed".to_string()))?;
-    debug!("Authorization UUID: {}", action_uuid.to_string());
+    if let Some(rejection) = state.maintenance.reject() {
+        return Err(rejection);
+    }
+    let limit = state.get_provider(&provider_name)
+        .and_then(|provider_config| provider_config.callback_per_minute)
+        .or(state.config().rate_limit.callback_per_minute);
+    check_login_rate(&state, "callback", &provider_name, addr.ip(), limit).await?;
+
+    let login_state: LoginState = state.signer().verify(&params.state)
+        .map_err(|_| ApiError::unauthorized("State verification failed").with_code("invalid_state"))?;
+    debug!("Authorization UUID: {}", login_state.nonce.to_string());
+
+    // 为某个提供者签发的 state 不能用在其它提供者的回调上
+    if login_state.provider != provider_name {
+        return Err(ApiError::bad_request("State was not issued for this provider").with_code("provider_mismatch"));
+    }
+
+    // 拒绝过期的 state，缩小被截获的 state 的重放窗口
+    let state_ttl = Duration::from_secs(state.config().oauth.state_ttl_secs);
+    let state_expired = SystemTime::now()
+        .duration_since(login_state.issued_at)
+        .map(|age| age > state_ttl)
+        .unwrap_or(true);
+    if state_expired {
+        return Err(ApiError::bad_request("Login state expired").with_code("state_expired"));
+    }
+
+    // state 必须由当前浏览器发起，否则可能是攻击者构造的回调链接
+    let bound_to_browser = jar.get(cookies::OAUTH_STATE_COOKIE).is_some_and(|cookie| {
+        csrf::constant_time_eq(cookie.value().as_bytes(), login_state.nonce.simple().to_string().as_bytes())
+    });
+    if !bound_to_browser {
+        return Err(ApiError::unauthorized("State was not issued to this browser").with_code("state_mismatch"));
+    }
+
+    // state 只能使用一次，超时或被淘汰的流程需要重新登录
+    if !state.pending_flows.finish(&login_state.nonce, &provider_name) {
+        return Err(ApiError::bad_request("Login flow expired or already used").with_code("flow_expired"));
+    }
 
     // 获取提供者配置
-    let provider_config = state
+    let mut provider_config = state
         .get_provider(&provider_name)
-        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Provider {} not found", provider_name)))?;
+        .ok_or_else(|| ApiError::not_found(format!("Provider {} not found", provider_name)))?;
+    check_visibility(&state, &provider_name, &provider_config, addr)?;
+    provider_config.scopes = login_state.scopes.clone();
     
     let redirect_uri = state.get_redirect_uri(&provider_name);
     
     // 根据提供者类型创建相应的 provider
-    let provider = create_oauth_provider(provider_config, &provider_name);
+    let provider = create_oauth_provider(&provider_config, &provider_name, &state.http_client(&provider_name))?;
     
-    // 1. 使用授权码交换访问令牌
-    let (access_token, expire_duration) = provider.exchange_token(&params.code, &redirect_uri).await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    // 1. 使用授权码交换访问令牌（登录时使用了 PKCE 则附带 code_verifier）
+    let code_verifier = jar.get(cookies::PKCE_VERIFIER_COOKIE).map(|cookie| cookie.value().to_string());
+    let grant = exchange_token_with_retry(&*provider, &provider_config, &params.code, &redirect_uri, code_verifier.as_deref()).await
+        .map_err(|e| provider_failure(&state, &provider_name, e))?;
 
-    debug!("Get a access token expiring in {}s", expire_duration.as_secs());
+    debug!("Get a access token expiring in {}s", grant.expires_in.as_secs());
+    let expire_duration = apply_expiry_jitter(grant.expires_in, state.config().oauth.expiry_jitter_secs);
+
+    // 提供者可能只授予部分权限（如 Blessing Skin 管理员限制了可用权限）
+    let scopes = retain_granted(&login_state.scopes, grant.granted_scopes.as_deref());
+    let denied: Vec<String> = login_state.scopes.iter()
+        .filter(|scope| !scopes.contains(scope))
+        .cloned()
+        .collect();
+    if !denied.is_empty() {
+        if provider_config.require_all_scopes {
+            state.events.record("login_rejected", Some(&provider_name), None, Some(format!("scopes denied: {:?}", denied)));
+            return Err(ApiError::forbidden(format!("Provider {} did not grant required scopes: {}", provider_name, denied.join(" ")))
+                .with_code("scope_denied")
+                .with_field("denied_scopes", denied));
+        }
+        info!("提供者 {} 未授予部分权限: {:?}", provider_name, denied);
+    }
     
     // 2. 获取用户信息
-    let user_info = provider.get_user_info(&access_token).await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let mut user_info = provider.get_user_info(&grant.access_token).await
+        .map_err(|e| provider_failure(&state, &provider_name, e))?;
+    state.provider_health.record_success(&provider_name);
+
+    if provider_config.require_profile && user_info.profiles.is_empty() {
+        state.events.record("login_rejected", Some(&provider_name), Some(&user_info.subject), Some("no profile".to_string()));
+        return Err(ApiError::forbidden(provider_config.no_profile_message.clone()).with_code("no_profile"));
+    }
+
+    for profile in &mut user_info.profiles {
+        profile.merge_properties(&provider_config.extra_profile_properties);
+    }
     
     debug!("用户信息获取成功: uid={}, nickname={}", user_info.uid, user_info.nickname);
+    state.events.record("login_succeeded", Some(&provider_name), Some(&user_info.subject), None);
     
     // 3. 创建 token 并设置 cookie
-    let token = TokenInformation {
-        access_token,
+    let token_claims = TokenInformation {
+        session_id: Uuid::new_v4(),
+        auth_time: SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        access_token: grant.access_token,
+        refresh_token: grant.refresh_token,
         provider_name,
+        scopes,
         user_info,
         expire_date: SystemTime::now() + expire_duration
-    }
-    .sign_with_key(state.secret())
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Token sign failed: {}", e)))?;
-    
-    let mut token_cookie = Cookie::new("access_token", token);
-    token_cookie.set_path("/");
-    token_cookie.set_http_only(true);
-    token_cookie.set_same_site(SameSite::Strict);
-    token_cookie.set_expires(time::OffsetDateTime::now_utc() + expire_duration);
+    };
+    let (token, expires) = sign_session(&state, &token_claims)
+        .map_err(|e| ApiError::internal(format!("Token sign failed: {}", e)))?;
     
-    let jar = jar.add(token_cookie);
+    let config = state.config();
+    // 单会话模式下使该用户之前的会话失效
+    if config.oauth.single_session_per_user {
+        state.sessions.replace(&token_claims.user_info.subject, token_claims.session_id);
+    }
+    let jar = cookies::add_session(&config.cookie, jar, token.clone(), expires)
+        .map_err(ApiError::internal)?
+        .add(csrf::new_csrf_cookie(&config.cookie, expires));
+    let jar = cookies::remove_flow_cookies(&config.cookie, jar);
     
-    // 重定向到首页
-    Ok((jar, Redirect::to("/")))
+    let success_mode = login_state.mode
+        .or(provider_config.callback_success_mode)
+        .unwrap_or(config.oauth.callback_success_mode);
+    Ok(match success_mode {
+        // 重定向到登录前请求的页面（默认为首页），只保留配置中列出的查询参数
+        CallbackSuccessMode::Redirect => {
+            let mut query = url::form_urlencoded::Serializer::new(String::new());
+            for name in &config.oauth.preserve_redirect_params {
+                if let Some(value) = params.extra.get(name) {
+                    query.append_pair(name, value);
+                }
+            }
+            let query = query.finish();
+            let redirect = login_state.redirect.as_deref().unwrap_or("/");
+            let target = if query.is_empty() {
+                redirect.to_string()
+            } else {
+                // 查询参数需要插在 fragment 之前
+                let (path, fragment) = redirect.split_once('#').map_or((redirect, None), |(path, fragment)| (path, Some(fragment)));
+                let separator = if path.contains('?') { '&' } else { '?' };
+                let fragment = fragment.map(|fragment| format!("#{}", fragment)).unwrap_or_default();
+                format!("{}{}{}{}", path, separator, query, fragment)
+            };
+            (jar, Redirect::to(&target)).into_response()
+        }
+        // 展示登录成功页面
+        CallbackSuccessMode::Page => {
+            let page = config.oauth.success_page_html.clone()
+                .unwrap_or_else(|| DEFAULT_SUCCESS_PAGE.to_string());
+            (jar, Html(page)).into_response()
+        }
+        // 返回与 cookie 相同的签名 token
+        CallbackSuccessMode::Json => {
+            let mut user = token_claims.user_info;
+            user.resolve_avatar(&config.avatar);
+            let expires_at = token_claims.expire_date
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            (jar, Json(serde_json::json!({
+                "token": token,
+                "expires_at": expires_at,
+                "user": user,
+            }))).into_response()
+        }
+    })
 }
 
 
 
+/// 按角色数据的修改时间处理条件请求
+///
+/// `If-Modified-Since` 不早于修改时间时返回 304，否则构造响应并附加 `Last-Modified`
+fn conditional_response(
+    headers: &HeaderMap,
+    last_modified: Option<u64>,
+    build: impl FnOnce() -> Response,
+) -> Response {
+    let Some(last_modified) = last_modified.map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs)) else {
+        return build();
+    };
+    let last_modified_header = [(header::LAST_MODIFIED, httpdate::fmt_http_date(last_modified))];
+
+    let not_modified = headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| httpdate::parse_http_date(value).ok())
+        .is_some_and(|since| last_modified <= since);
+    if not_modified {
+        return (StatusCode::NOT_MODIFIED, last_modified_header).into_response();
+    }
+    (last_modified_header, build()).into_response()
+}
+
 /// 获取当前用户信息
 /// 
 /// 此函数依赖于 auth_middleware 将用户信息注入到请求的 extensions 中
-pub async fn get_user(user: UnifiedUserInfo) -> Json<UnifiedUserInfo> {
+pub async fn get_user(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    mut user: UnifiedUserInfo,
+) -> Response {
     debug!("返回用户信息: uid={}, nickname={}", user.uid, user.nickname);
-    Json(user)
+    conditional_response(&headers, user.last_modified, || {
+        user.resolve_avatar(&state.config().avatar);
+        Json(user).into_response()
+    })
+}
+
+/// 获取当前用户的角色列表（流式输出）
+///
+/// 以分块 JSON 数组的形式逐个序列化角色，角色数量很多时也不需要一次性构造完整响应体
+pub async fn get_profiles(headers: HeaderMap, user: UnifiedUserInfo) -> Response {
+    debug!("流式返回角色列表: uid={}, count={}", user.uid, user.profiles.len());
+    conditional_response(&headers, user.last_modified, || stream_profiles(user.profiles))
+}
+
+/// 构造角色列表的流式响应
+fn stream_profiles(profiles: Vec<YggdrasilProfile>) -> Response {
+    let items = profiles.into_iter().enumerate().map(|(i, profile)| {
+        let mut chunk = Vec::new();
+        if i > 0 {
+            chunk.push(b',');
+        }
+        serde_json::to_writer(&mut chunk, &profile).map(|_| Bytes::from(chunk))
+    });
+    let stream = stream::once(async { Ok(Bytes::from_static(b"[")) })
+        .chain(stream::iter(items))
+        .chain(stream::once(async { Ok(Bytes::from_static(b"]")) }));
+
+    (
+        [(header::CONTENT_TYPE, "application/json")],
+        Body::from_stream(stream),
+    ).into_response()
+}
+
+/// 校验并内省会话 token（RFC 7662 风格）
+///
+/// 只允许受信任地址段或携带共享密钥的内部服务调用；
+/// token 无效或已过期时返回 `{"active": false}`
+pub async fn introspect(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Form(request): Form<IntrospectRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let config = state.config();
+    let secret_ok = match (&config.server.introspection_secret, headers.get(INTROSPECTION_SECRET_HEADER)) {
+        (Some(secret), Some(header)) => csrf::constant_time_eq(secret.as_bytes(), header.as_bytes()),
+        _ => false,
+    };
+    if !secret_ok && !state.is_trusted_ip(addr.ip()) {
+        return Err(ApiError::unauthorized("Introspection not allowed"));
+    }
+
+    let claims: Option<TokenInformation> = state.signer().verify(&request.token).ok();
+    Ok(Json(match claims {
+        Some(claims) if SystemTime::now() <= claims.expire_date
+            && !is_revoked(&state, &claims)
+            && !(config.oauth.single_session_per_user
+                && state.sessions.is_superseded(&claims.user_info.subject, &claims.session_id)) => serde_json::json!({
+            "active": true,
+            "sub": claims.user_info.subject,
+            "uid": claims.user_info.uid,
+            "provider": claims.provider_name,
+            "exp": claims.expire_date
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        }),
+        _ => serde_json::json!({ "active": false }),
+    }))
+}
+
+/// 当前会话的状态，供前端显示剩余时间并在过期前主动刷新
+///
+/// 只校验会话 token 本身，不访问提供者；未登录或会话无效时返回 `{"authenticated": false}`
+pub async fn get_session(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Json<serde_json::Value> {
+    Json(match active_session(&state, &headers) {
+        Some(claims) => serde_json::json!({
+            "authenticated": true,
+            "provider": claims.provider_name,
+            "nickname": claims.user_info.nickname,
+            "expires_at": claims.expire_date
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        }),
+        None => serde_json::json!({ "authenticated": false }),
+    })
+}
+
+/// 主动刷新会话
+///
+/// 使用会话中保存的刷新令牌向提供者换取新的访问令牌，并通过 cookie 下发新的会话；
+/// 已过期但仍在 `refresh_grace_period_secs` 宽限期内的会话同样可以刷新。
+/// 只接受 cookie 中的会话，会话没有刷新令牌时返回 401 `refresh_unavailable`
+pub async fn refresh(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+) -> Result<(CookieJar, Json<serde_json::Value>), (CookieJar, ApiError)> {
+    let config = state.config();
+    let Some(token_value) = cookies::session_token(&jar) else {
+        return Err((jar, ApiError::unauthorized("Not authenticated").with_code("not_authenticated")));
+    };
+    let clear_session = |jar: CookieJar| cookies::remove_session(&config.cookie, jar);
+
+    let token_claims: TokenInformation = match state.signer().verify(&token_value) {
+        Ok(x) => x,
+        Err(_) => {
+            return Err((clear_session(jar), ApiError::unauthorized("Invalid token").with_code("invalid_token")));
+        }
+    };
+    if config.oauth.single_session_per_user
+        && state.sessions.is_superseded(&token_claims.user_info.subject, &token_claims.session_id)
+    {
+        return Err((clear_session(jar), ApiError::unauthorized("Logged in elsewhere").with_code("logged_in_elsewhere")));
+    }
+    if is_revoked(&state, &token_claims) {
+        return Err((clear_session(jar), ApiError::unauthorized("Session revoked").with_code("session_revoked")));
+    }
+    let grace = Duration::from_secs(config.oauth.refresh_grace_period_secs);
+    if SystemTime::now().duration_since(token_claims.expire_date).is_ok_and(|overdue| overdue > grace) {
+        return Err((clear_session(jar), ApiError::unauthorized("Login token expired").with_code("token_expired")));
+    }
+    if token_claims.refresh_token.is_none() {
+        return Err((jar, ApiError::unauthorized("No refresh token stored for this session").with_code("refresh_unavailable")));
+    }
+
+    let subject = token_claims.user_info.subject.clone();
+    let token_claims = match refresh_session(&state, &token_claims).await {
+        Ok(claims) => claims,
+        Err(e) => {
+            debug!("会话刷新失败: {}", e);
+            state.events.record("session_refresh_failed", Some(&token_claims.provider_name), Some(&subject), Some(e.to_string()));
+            // 上游故障时保留会话，客户端可以稍后重试；其它失败需要重新登录
+            if e.is_upstream_fault() {
+                return Err((jar, e.into()));
+            }
+            return Err((jar, ApiError::unauthorized("Session refresh failed").with_code("refresh_failed")));
+        }
+    };
+    state.events.record("session_refreshed", Some(&token_claims.provider_name), Some(&subject), None);
+
+    let (token, expires) = sign_session(&state, &token_claims)
+        .map_err(|e| (jar.clone(), ApiError::internal(format!("Token sign failed: {}", e))))?;
+    let session_jar = cookies::add_session(&config.cookie, jar.clone(), token, expires)
+        .map_err(|e| (jar.clone(), ApiError::internal(e)))?;
+    let session_jar = session_jar.add(csrf::renew_csrf_cookie(&config.cookie, &jar, expires));
+    let expires_at = token_claims.expire_date
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    Ok((session_jar, Json(serde_json::json!({ "expires_at": expires_at }))))
 }
 
 /// 登出
-pub async fn logout(jar: CookieJar) -> impl IntoResponse {
+///
+/// 在服务端吊销当前会话，复制出去的 token 也随之失效；
+/// 会话所属提供者开启了 `single_logout` 且有登出地址时，经提供者登出后再回到首页
+pub async fn logout(State(state): State<Arc<AppState>>, jar: CookieJar) -> impl IntoResponse {
     info!("用户登出");
+    state.events.record("logout", None, None, None);
     
-    let mut token_cookie = Cookie::from("access_token");
-    token_cookie.set_path("/");
-    
-    let jar = jar.remove(token_cookie);
+    let config = state.config();
+    let post_logout_redirect = format!("{}/", config.oauth.prefix_url);
+    let claims = cookies::session_token(&jar)
+        .and_then(|token| state.signer().verify::<TokenInformation>(&token).ok());
+    if let Some(claims) = &claims {
+        let max_lifetime = Duration::from_secs(config.oauth.max_session_lifetime_secs);
+        state.sessions.revoke(claims.session_id, revocation_deadline(claims, max_lifetime));
+    }
+    let provider_logout_url = claims
+        .and_then(|claims| {
+            let provider_config = state.get_provider(&claims.provider_name)
+                .filter(|provider_config| provider_config.single_logout)?;
+            create_oauth_provider(&provider_config, &claims.provider_name, &state.http_client(&claims.provider_name))
+                .ok()?
+                .end_session_url(&post_logout_redirect)
+        });
+
+    let jar = cookies::remove_session(&config.cookie, jar)
+        .remove(csrf::removal_cookie(&config.cookie));
     
-    (jar, Redirect::to("/"))
+    (jar, Redirect::to(provider_logout_url.as_deref().unwrap_or("/")))
+}
+
+/// 登出所有会话
+///
+/// 吊销当前用户在此之前认证的全部会话（包括其它设备上的），并清除当前 cookie
+pub async fn logout_all(
+    State(state): State<Arc<AppState>>,
+    user: UnifiedUserInfo,
+    jar: CookieJar,
+) -> impl IntoResponse {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let max_lifetime = Duration::from_secs(state.config().oauth.max_session_lifetime_secs);
+    state.sessions.revoke_all(&user.subject, now, max_lifetime);
+    info!(target: "audit", "已吊销用户的所有会话: subject={}", user.subject);
+    state.events.record("logout_all", Some(&user.provider), Some(&user.subject), None);
+
+    let config = state.config();
+    let jar = cookies::remove_session(&config.cookie, jar)
+        .remove(csrf::removal_cookie(&config.cookie));
+    (jar, StatusCode::NO_CONTENT)
+}
+
+/// 读取 `Authorization: Bearer <token>` 请求头中的会话 token
+///
+/// token 格式与会话 cookie 的值相同，供无法使用 cookie 的客户端使用
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    let token = token.trim();
+    (scheme.eq_ignore_ascii_case("Bearer") && !token.is_empty()).then_some(token)
 }
 
 /// 认证中间件
@@ -334,26 +1458,102 @@ pub async fn logout(jar: CookieJar) -> impl IntoResponse {
 pub async fn auth_middleware(
     State(state): State<Arc<AppState>>,
     jar: CookieJar,
+    request: Request,
+    next: Next,
+) -> Result<Response, (CookieJar, ApiError)> {
+    let started = Instant::now();
+    let result = authenticate(state.clone(), jar, request, next).await;
+    if result.is_err() {
+        pad_auth_failure(&state, started).await;
+    }
+    result
+}
+
+async fn authenticate(
+    state: Arc<AppState>,
+    jar: CookieJar,
     mut request: Request,
     next: Next,
-) -> Result<Response, (StatusCode, CookieJar, String)> {
-    // 从 cookie 中获取 token
-    let token_cookie = match jar.get("access_token") {
+) -> Result<Response, (CookieJar, ApiError)> {
+    // 优先使用 Authorization: Bearer 请求头中的 token，其次使用 cookie
+    let header_token = bearer_token(request.headers()).map(str::to_string);
+    let from_header = header_token.is_some();
+    let token_value = match header_token.or_else(|| cookies::session_token(&jar)) {
         Some(x) => x,
-        None => { return Err((StatusCode::UNAUTHORIZED, jar, "Not authenticated".to_string())); }
+        None => { return Err((jar, ApiError::unauthorized("Not authenticated").with_code("not_authenticated"))); }
     };
 
+    let config = state.config();
+    // 请求头中的 token 失效时不需要清除 cookie
+    let clear_session = |jar: CookieJar| if from_header { jar } else { cookies::remove_session(&config.cookie, jar) };
+
     // 验证并解析 token
-    let token_claims: TokenInformation = match token_cookie.value().verify_with_key(state.secret()) {
+    let mut token_claims: TokenInformation = match state.signer().verify(&token_value) {
         Ok(x) => x,
         Err(_) => {
-            return Err((StatusCode::UNAUTHORIZED, jar.remove(Cookie::from("access_token")), "Invalid token".to_string()));
+            return Err((clear_session(jar), ApiError::unauthorized("Invalid token").with_code("invalid_token")));
+        }
+    };
+
+    // 单会话模式下，已被之后的登录取代的会话不再有效
+    if config.oauth.single_session_per_user
+        && state.sessions.is_superseded(&token_claims.user_info.subject, &token_claims.session_id)
+    {
+        return Err((clear_session(jar), ApiError::unauthorized("Logged in elsewhere").with_code("logged_in_elsewhere")));
+    }
+
+    // 已登出或被“登出所有会话”吊销的会话不再有效
+    if is_revoked(&state, &token_claims) {
+        return Err((clear_session(jar), ApiError::unauthorized("Session revoked").with_code("session_revoked")));
+    }
+
+    // 检查 token 是否过期；刚过期且仍在宽限期内、或即将过期时尝试使用刷新令牌续期
+    let mut refreshed_jar = None;
+    let now = SystemTime::now();
+    let is_expired = now > token_claims.expire_date;
+    let expired = || (
+        clear_session(jar.clone()),
+        ApiError::unauthorized("Login token expired").with_code("token_expired"),
+    );
+    let refresh_due = if is_expired {
+        let grace = Duration::from_secs(config.oauth.refresh_grace_period_secs);
+        let within_grace = now.duration_since(token_claims.expire_date)
+            .is_ok_and(|overdue| overdue <= grace);
+
+        // 续期后的 token 只能通过 cookie 下发，请求头中的 token 过期后需要重新登录
+        if !within_grace || token_claims.refresh_token.is_none() || from_header {
+            return Err(expired());
         }
+        true
+    } else {
+        let ahead = Duration::from_secs(config.oauth.refresh_ahead_secs);
+        config.oauth.refresh_ahead_secs > 0
+            && token_claims.refresh_token.is_some()
+            && !from_header
+            && token_claims.expire_date.duration_since(now).is_ok_and(|remaining| remaining <= ahead)
     };
 
-    // 检查 token 是否过期
-    if SystemTime::now() > token_claims.expire_date {
-        return Err((StatusCode::UNAUTHORIZED, jar.remove(Cookie::from("access_token")), "Login token expired".to_string()));
+    if refresh_due {
+        let subject = token_claims.user_info.subject.clone();
+        match refresh_session(&state, &token_claims).await {
+            Ok(claims) => {
+                token_claims = claims;
+                state.events.record("session_refreshed", Some(&token_claims.provider_name), Some(&subject), None);
+                let (token, expires) = sign_session(&state, &token_claims)
+                    .map_err(|e| (jar.clone(), ApiError::internal(format!("Token sign failed: {}", e))))?;
+                let session_jar = cookies::add_session(&config.cookie, jar.clone(), token, expires)
+                    .map_err(|e| (jar.clone(), ApiError::internal(e)))?;
+                refreshed_jar = Some(session_jar.add(csrf::renew_csrf_cookie(&config.cookie, &jar, expires)));
+            }
+            Err(e) => {
+                debug!("会话刷新失败: {}", e);
+                state.events.record("session_refresh_failed", Some(&token_claims.provider_name), Some(&subject), Some(e.to_string()));
+                // 提前续期失败时原会话仍然有效，继续处理请求
+                if is_expired {
+                    return Err(expired());
+                }
+            }
+        }
     }
 
     // 从 OAuth 服务器获取用户信息
@@ -361,9 +1561,113 @@ pub async fn auth_middleware(
 
     debug!("User authorized: {user_info:?}");
 
-    // 将用户信息存储到请求的 extensions 中
+    let session = SessionInfo {
+        provider: token_claims.provider_name,
+        scopes: token_claims.scopes,
+        auth_time: token_claims.auth_time,
+        expires_at: token_claims.expire_date
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+    };
+
+    // 将用户信息与会话信息存储到请求的 extensions 中
     request.extensions_mut().insert(user_info);
+    request.extensions_mut().insert(session);
+
+    // 继续处理请求，会话被刷新时一并下发新的 cookie
+    let response = next.run(request).await;
+    Ok(match refreshed_jar {
+        Some(jar) => (jar, response).into_response(),
+        None => response,
+    })
+}
 
-    // 继续处理请求
-    Ok(next.run(request).await)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoint(base: &str, path: &str) -> String {
+        OAuthProviderType::BlessingSkin(base.to_string()).endpoint(path).unwrap().to_string()
+    }
+
+    #[test]
+    fn endpoint_joins_base_without_path() {
+        assert_eq!(endpoint("https://littleskin.cn", "oauth/token"), "https://littleskin.cn/oauth/token");
+        assert_eq!(endpoint("https://littleskin.cn/", "oauth/token"), "https://littleskin.cn/oauth/token");
+    }
+
+    #[test]
+    fn endpoint_keeps_base_path_prefix() {
+        assert_eq!(endpoint("https://host/skin", "oauth/token"), "https://host/skin/oauth/token");
+        assert_eq!(endpoint("https://host/skin/", "oauth/token"), "https://host/skin/oauth/token");
+    }
+
+    #[test]
+    fn endpoint_ignores_leading_slash_in_path() {
+        assert_eq!(endpoint("https://host/skin", "/api/user"), "https://host/skin/api/user");
+        assert_eq!(endpoint("https://host/skin/", "/api/user"), "https://host/skin/api/user");
+    }
+
+    fn session_claims(session_id: Uuid, auth_time: u64, expire_date: SystemTime) -> TokenInformation {
+        TokenInformation {
+            session_id,
+            auth_time,
+            access_token: "access".to_string(),
+            refresh_token: Some("refresh".to_string()),
+            provider_name: "littleskin".to_string(),
+            scopes: Vec::new(),
+            expire_date,
+            user_info: UnifiedUserInfo {
+                subject: "littleskin:1".to_string(),
+                uid: "1".to_string(),
+                nickname: "alice".to_string(),
+                email: "alice@example.com".to_string(),
+                provider: "littleskin".to_string(),
+                provider_type: OAuthProviderType::BlessingSkin("https://littleskin.cn".to_string()),
+                profiles: Vec::new(),
+                avatar_url: None,
+                last_modified: None,
+                primary_profile: None,
+            },
+        }
+    }
+
+    #[test]
+    fn logout_with_stale_cookie_keeps_refreshed_copy_revoked() {
+        let hour = Duration::from_secs(60 * 60);
+        let now = SystemTime::now();
+        let auth_time = (now - 3 * hour).duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+        let session_id = Uuid::new_v4();
+        // 旧 cookie 已过期，复制出去的副本刷新后仍然有效
+        let stale = session_claims(session_id, auth_time, now - 2 * hour);
+        let refreshed = session_claims(session_id, auth_time, now + hour);
+
+        let sessions = session::SessionRegistry::default();
+        sessions.revoke(stale.session_id, revocation_deadline(&stale, 24 * hour));
+        // 之后的吊销会清理过期记录，旧 cookie 的过期时间不能让这条记录被丢弃
+        sessions.revoke(Uuid::new_v4(), now + hour);
+
+        let subject = &refreshed.user_info.subject;
+        assert!(sessions.is_revoked(subject, &refreshed.session_id, refreshed.auth_time));
+    }
+
+    #[tokio::test]
+    async fn exhausted_login_budget_does_not_throttle_other_providers() {
+        use crate::rate_limit::{RateLimitBackend, memory::MemoryRateLimiter};
+
+        let limiter = MemoryRateLimiter::default();
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+        let littleskin = login_rate_key("login", "littleskin", ip);
+        let microsoft = login_rate_key("login", "microsoft", ip);
+        assert!(limiter.try_record(&littleskin, 1, 1, LOGIN_RATE_WINDOW).await.unwrap().is_ok());
+        assert!(limiter.try_record(&littleskin, 1, 1, LOGIN_RATE_WINDOW).await.unwrap().is_err());
+        assert!(limiter.try_record(&microsoft, 1, 1, LOGIN_RATE_WINDOW).await.unwrap().is_ok());
+    }
+
+    #[test]
+    fn microsoft_endpoint_uses_fixed_base() {
+        let url = OAuthProviderType::Microsoft.endpoint("common/oauth2/v2.0/token").unwrap();
+        assert_eq!(url.as_str(), "https://login.microsoftonline.com/common/oauth2/v2.0/token");
+    }
 }
@@ -0,0 +1,111 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use std::{collections::BTreeMap, sync::{Arc, RwLock}, time::Duration};
+use tracing::{info, warn};
+
+use crate::{AppState, config::OAuthProviderConfig};
+
+/// 启动时提供者连通性预检的结果
+///
+/// 未开启 `verify_providers` 时为空，`/readyz` 视为就绪
+#[derive(Default)]
+pub struct ProviderReachability {
+    results: RwLock<Option<BTreeMap<String, bool>>>,
+}
+
+impl ProviderReachability {
+    fn set(&self, results: BTreeMap<String, bool>) {
+        *self.results.write().expect("reachability lock poisoned") = Some(results);
+    }
+
+    fn snapshot(&self) -> Option<BTreeMap<String, bool>> {
+        self.results.read().expect("reachability lock poisoned").clone()
+    }
+}
+
+/// 检查提供者的授权/令牌主机能否连通
+///
+/// 只要收到任何 HTTP 响应（包括 4xx/5xx）就认为可达，DNS 解析失败、连接失败或超时视为不可达
+async fn check_provider(client: &reqwest::Client, provider: &OAuthProviderConfig, timeout: Duration) -> Result<(), String> {
+    let url = provider.provider_type.endpoint("").map_err(|e| e.to_string())?;
+    client.head(url).timeout(timeout).send().await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// 并行检查所有启用的提供者，总耗时不超过 `timeout`
+///
+/// 返回 (提供者名称, 提供者配置, 检查结果)
+async fn check_enabled_providers(
+    state: &AppState,
+    timeout: Duration,
+) -> Vec<(String, OAuthProviderConfig, Result<(), String>)> {
+    let checks = state.get_enabled_providers().into_iter().map(|(name, provider)| {
+        let client = state.http_client(&name);
+        async move {
+            let result = match tokio::time::timeout(timeout, check_provider(&client, &provider, timeout)).await {
+                Ok(result) => result,
+                Err(_) => Err("timed out".to_string()),
+            };
+            (name, provider, result)
+        }
+    });
+    futures::future::join_all(checks).await
+}
+
+/// 并行预检所有启用的提供者，耗时不超过 `verify_timeout_secs`
+pub async fn verify_providers(state: &AppState) {
+    let timeout = Duration::from_secs(state.config().oauth.verify_timeout_secs);
+    let results = check_enabled_providers(state, timeout).await
+        .into_iter()
+        .map(|(name, _, result)| {
+            match &result {
+                Ok(()) => info!("提供者 {} 连通性检查通过", name),
+                Err(e) => warn!("提供者 {} 连通性检查失败: {}", name, e),
+            }
+            (name, result.is_ok())
+        })
+        .collect();
+    state.provider_reachability.set(results);
+}
+
+/// 实时就绪检查
+///
+/// 每次请求都会并行检查所有启用的提供者（受 `request_timeout_secs` 限制），
+/// 所有 `required_for_readiness` 的提供者都可达时返回 200，否则返回 503
+pub async fn ready(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let timeout = Duration::from_secs(state.config().oauth.request_timeout_secs);
+    let results = check_enabled_providers(&state, timeout).await;
+
+    let ready = results.iter().all(|(_, provider, result)| !provider.required_for_readiness || result.is_ok());
+    let providers: BTreeMap<_, _> = results
+        .into_iter()
+        .map(|(name, provider, result)| {
+            let status = serde_json::json!({
+                "reachable": result.is_ok(),
+                "required": provider.required_for_readiness,
+                "error": result.err(),
+            });
+            (name, status)
+        })
+        .collect();
+
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(serde_json::json!({
+        "ready": ready,
+        "providers": providers,
+    })))
+}
+
+/// 就绪检查
+///
+/// 预检过的提供者中至少有一个可达（或没有启用提供者）时返回 200，否则返回 503。
+/// 单个提供者不可达不会让整个服务下线，具体情况见响应中的 `providers`
+pub async fn readyz(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let results = state.provider_reachability.snapshot();
+    let ready = results.as_ref().is_none_or(|results| results.is_empty() || results.values().any(|ok| *ok));
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(serde_json::json!({
+        "ready": ready,
+        "providers": results.unwrap_or_default(),
+    })))
+}
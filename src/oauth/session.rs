@@ -0,0 +1,102 @@
+use std::{collections::HashMap, sync::Mutex, time::{Duration, SystemTime}};
+use uuid::Uuid;
+
+/// 服务端的会话记录（单会话登录与会话吊销）
+///
+/// 会话本身仍是无状态的签名 token，这里只记录每个 subject 最近一次登录签发的会话 ID，
+/// 以及已被吊销的会话。两个登录并发完成时，后写入的一方生效，另一方的会话会在下一次请求时被拒绝。
+/// 记录只保存在内存中，重启后旧会话在过期前（或用户再次登录前）仍然有效。
+#[derive(Default)]
+pub struct SessionRegistry {
+    current: Mutex<HashMap<String, Uuid>>,
+    /// 已吊销的会话 ID 及记录的保留期限（会话超过最长存续时间之后即可丢弃）
+    revoked: Mutex<HashMap<Uuid, SystemTime>>,
+    /// 每个用户的吊销时间点（Unix 时间戳，秒），不晚于该时间认证的会话全部失效；
+    /// 超过会话最长存续时间后丢弃
+    revoked_before: Mutex<HashMap<String, u64>>,
+}
+
+impl SessionRegistry {
+    /// 记录用户新签发的会话，之前的会话随即失效
+    pub fn replace(&self, subject: &str, session_id: Uuid) {
+        self.current
+            .lock()
+            .expect("session registry lock poisoned")
+            .insert(subject.to_string(), session_id);
+    }
+
+    /// 会话是否已被同一用户之后的登录取代
+    pub fn is_superseded(&self, subject: &str, session_id: &Uuid) -> bool {
+        self.current
+            .lock()
+            .expect("session registry lock poisoned")
+            .get(subject)
+            .is_some_and(|current| current != session_id)
+    }
+
+    /// 吊销单个会话，记录保留到 `until` 为止
+    pub fn revoke(&self, session_id: Uuid, until: SystemTime) {
+        let mut revoked = self.revoked.lock().expect("session registry lock poisoned");
+        // 顺便清理已经过了保留期限的记录
+        let now = SystemTime::now();
+        revoked.retain(|_, until| *until > now);
+        revoked.insert(session_id, until);
+    }
+
+    /// 吊销用户在 `auth_time`（Unix 时间戳，秒）及之前认证的所有会话
+    ///
+    /// 只精确到秒，同一秒内新登录的会话也会失效。
+    /// `max_lifetime` 为会话的最长存续时间，超过该时间的吊销记录所覆盖的会话都已失效，可以丢弃
+    pub fn revoke_all(&self, subject: &str, auth_time: u64, max_lifetime: Duration) {
+        let mut revoked_before = self.revoked_before.lock().expect("session registry lock poisoned");
+        // 顺便清理已经没有有效会话的记录
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        revoked_before.retain(|_, before| before.saturating_add(max_lifetime.as_secs()) > now);
+        revoked_before.insert(subject.to_string(), auth_time);
+    }
+
+    /// 会话是否已被吊销
+    pub fn is_revoked(&self, subject: &str, session_id: &Uuid, auth_time: u64) -> bool {
+        let revoked = self.revoked
+            .lock()
+            .expect("session registry lock poisoned")
+            .contains_key(session_id);
+        revoked || self.revoked_before
+            .lock()
+            .expect("session registry lock poisoned")
+            .get(subject)
+            .is_some_and(|before| auth_time <= *before)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    fn now() -> u64 {
+        SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs()
+    }
+
+    #[test]
+    fn revoke_all_revokes_the_calling_session() {
+        // 删除账号与“登出所有会话”都以当前时间吊销，发起请求的会话本身也会失效
+        let sessions = SessionRegistry::default();
+        let auth_time = now() - 60;
+        sessions.revoke_all("alice", now(), DAY);
+        assert!(sessions.is_revoked("alice", &Uuid::new_v4(), auth_time));
+        assert!(!sessions.is_revoked("bob", &Uuid::new_v4(), auth_time));
+    }
+
+    #[test]
+    fn session_authenticated_after_revoke_all_is_valid() {
+        let sessions = SessionRegistry::default();
+        let revoked_at = now() - 60;
+        sessions.revoke_all("alice", revoked_at, DAY);
+        assert!(!sessions.is_revoked("alice", &Uuid::new_v4(), revoked_at + 1));
+    }
+}
@@ -0,0 +1,85 @@
+use axum::{
+    body::{Body, to_bytes},
+    extract::{Request, State},
+    http::{HeaderMap, HeaderValue, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+
+use crate::{AppState, error::ApiError};
+
+/// RFC 9457 Problem Details 的媒体类型
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// 纯文本错误体的最大读取长度，超过时省略 detail
+const MAX_DETAIL_BYTES: usize = 64 * 1024;
+
+/// 客户端是否通过 `Accept` 请求 Problem Details
+fn accepts_problem_json(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|media| media.split(';').next().is_some_and(|m| m.trim().eq_ignore_ascii_case(PROBLEM_JSON)))
+}
+
+/// 错误响应是否为纯文本（如 axum 提取器的拒绝响应）
+fn is_plain_text(response: &Response) -> bool {
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/plain"))
+}
+
+/// 将错误响应转换为 RFC 9457 `application/problem+json`
+///
+/// 配置 `server.problem_details` 开启时对所有请求生效，
+/// 否则只对 `Accept` 中包含 `application/problem+json` 的请求生效。
+/// `ApiError` 的 `code` 与额外字段作为扩展成员保留；其它 JSON 错误响应保持原样。
+pub async fn problem_details(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let wanted = state.config().server.problem_details || accepts_problem_json(request.headers());
+    let instance = request.uri().path().to_string();
+    let response = next.run(request).await;
+
+    let status = response.status();
+    if !wanted || !(status.is_client_error() || status.is_server_error()) {
+        return response;
+    }
+    let api_error = response.extensions().get::<ApiError>().cloned();
+    if api_error.is_none() && !is_plain_text(&response) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let mut problem = match &api_error {
+        Some(api_error) => {
+            let mut problem = api_error.extra().clone();
+            problem.insert("code".to_string(), api_error.code().into());
+            problem.insert("detail".to_string(), api_error.message().into());
+            problem
+        }
+        None => {
+            let detail = to_bytes(body, MAX_DETAIL_BYTES).await
+                .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+                .unwrap_or_default();
+            let mut problem = serde_json::Map::new();
+            problem.insert("detail".to_string(), detail.into());
+            problem
+        }
+    };
+    problem.insert("type".to_string(), "about:blank".into());
+    problem.insert("title".to_string(), status.canonical_reason().unwrap_or("Error").into());
+    problem.insert("status".to_string(), status.as_u16().into());
+    problem.insert("instance".to_string(), instance.into());
+
+    parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+    parts.headers.remove(header::CONTENT_LENGTH);
+    (parts, Body::from(serde_json::Value::Object(problem).to_string())).into_response()
+}
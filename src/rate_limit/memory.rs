@@ -0,0 +1,146 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::{collections::{HashMap, VecDeque}, sync::Mutex, time::{Duration, Instant}};
+
+use super::RateLimitBackend;
+
+/// 一个 key 在窗口内的用量
+#[derive(Default)]
+struct Usage {
+    /// 最近一次使用的窗口长度，清理其它 key 时按它判断是否过期
+    window: Duration,
+    entries: VecDeque<(Instant, u64)>,
+}
+
+impl Usage {
+    /// 移除已经滑出窗口的用量
+    fn prune(&mut self, now: Instant) {
+        while self.entries.front().is_some_and(|(time, _)| now.duration_since(*time) >= self.window) {
+            self.entries.pop_front();
+        }
+    }
+
+    fn used(&self) -> u64 {
+        self.entries.iter().map(|(_, amount)| amount).sum()
+    }
+
+    /// 从最早的用量开始，找到移出窗口后足够容纳 `amount` 的时间点
+    fn wait_for(&self, amount: u64, limit: u64, now: Instant) -> Duration {
+        let mut left = self.used();
+        if left + amount <= limit {
+            return Duration::ZERO;
+        }
+        for (time, entry) in &self.entries {
+            left -= entry;
+            if left + amount <= limit {
+                return self.window.saturating_sub(now.duration_since(*time));
+            }
+        }
+        self.window
+    }
+}
+
+/// 内存中的限流计数
+///
+/// 记录只保存在当前进程中，重启后清零；多个实例之间互不共享
+#[derive(Default)]
+pub struct MemoryRateLimiter {
+    entries: Mutex<HashMap<String, Usage>>,
+}
+
+impl MemoryRateLimiter {
+    /// 取出 key 的用量（不存在时插入）以便记录，并顺便清理窗口内已经没有用量的其它 key
+    fn with_usage<T>(&self, key: &str, window: Duration, f: impl FnOnce(&mut Usage, Instant) -> T) -> T {
+        let mut entries = self.entries.lock().expect("rate limiter lock poisoned");
+        let now = Instant::now();
+        entries.retain(|_, usage| {
+            usage.prune(now);
+            !usage.entries.is_empty()
+        });
+        let usage = entries.entry(key.to_string()).or_default();
+        usage.window = window;
+        usage.prune(now);
+        f(usage, now)
+    }
+
+    /// 只读地查看 key 的用量，key 不存在时按没有用量计算，不会插入新的记录
+    fn peek_usage<T>(&self, key: &str, window: Duration, f: impl FnOnce(&Usage, Instant) -> T) -> T {
+        let mut entries = self.entries.lock().expect("rate limiter lock poisoned");
+        let now = Instant::now();
+        match entries.get_mut(key) {
+            Some(usage) => {
+                usage.window = window;
+                usage.prune(now);
+                f(usage, now)
+            }
+            None => f(&Usage { window, entries: VecDeque::new() }, now),
+        }
+    }
+}
+
+#[async_trait]
+impl RateLimitBackend for MemoryRateLimiter {
+    async fn remaining(&self, key: &str, limit: u64, window: Duration) -> Result<u64> {
+        Ok(self.peek_usage(key, window, |usage, _| limit.saturating_sub(usage.used())))
+    }
+
+    async fn record(&self, key: &str, amount: u64, window: Duration) -> Result<()> {
+        self.with_usage(key, window, |usage, now| usage.entries.push_back((now, amount)));
+        Ok(())
+    }
+
+    async fn try_record(&self, key: &str, amount: u64, limit: u64, window: Duration) -> Result<Result<(), Duration>> {
+        Ok(self.with_usage(key, window, |usage, now| {
+            if usage.used() + amount <= limit {
+                usage.entries.push_back((now, amount));
+                Ok(())
+            } else {
+                Err(usage.wait_for(amount, limit, now))
+            }
+        }))
+    }
+
+    async fn retry_after(&self, key: &str, amount: u64, limit: u64, window: Duration) -> Result<Duration> {
+        Ok(self.peek_usage(key, window, |usage, now| usage.wait_for(amount, limit, now)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    #[tokio::test]
+    async fn try_record_rejects_over_limit() {
+        let limiter = MemoryRateLimiter::default();
+        assert!(limiter.try_record("alice", 2, 3, MINUTE).await.unwrap().is_ok());
+        let after = limiter.try_record("alice", 2, 3, MINUTE).await.unwrap().unwrap_err();
+        assert!(after > Duration::ZERO && after <= MINUTE);
+        assert_eq!(limiter.remaining("alice", 3, MINUTE).await.unwrap(), 1);
+        assert_eq!(limiter.remaining("bob", 3, MINUTE).await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn queries_do_not_insert_unseen_keys() {
+        let limiter = MemoryRateLimiter::default();
+        assert_eq!(limiter.remaining("login:203.0.113.7", 3, MINUTE).await.unwrap(), 3);
+        assert_eq!(limiter.retry_after("login:203.0.113.8", 1, 3, MINUTE).await.unwrap(), Duration::ZERO);
+        assert!(limiter.entries.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn amount_above_limit_waits_for_whole_window() {
+        let limiter = MemoryRateLimiter::default();
+        assert_eq!(limiter.retry_after("alice", 4, 3, MINUTE).await.unwrap(), MINUTE);
+    }
+
+    #[tokio::test]
+    async fn keys_with_short_window_do_not_prune_longer_windows() {
+        let limiter = MemoryRateLimiter::default();
+        limiter.record("upload_bytes:alice", 5, Duration::from_secs(60 * 60)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        limiter.record("login:127.0.0.1", 1, Duration::from_millis(1)).await.unwrap();
+        assert_eq!(limiter.remaining("upload_bytes:alice", 10, Duration::from_secs(60 * 60)).await.unwrap(), 5);
+    }
+}
@@ -0,0 +1,80 @@
+pub mod memory;
+pub mod redis;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::config::RateLimitBackendConfig;
+
+/// 限流计数的存储后端
+///
+/// 按 key 统计滑动窗口内的用量（请求次数、字节数等）。
+/// 内存后端只在单个进程内计数，多实例部署时使用 Redis 后端在所有实例之间共享计数
+#[async_trait]
+pub trait RateLimitBackend: Send + Sync {
+    /// 窗口内还能使用的额度
+    async fn remaining(&self, key: &str, limit: u64, window: Duration) -> Result<u64>;
+
+    /// 记录一笔用量
+    async fn record(&self, key: &str, amount: u64, window: Duration) -> Result<()>;
+
+    /// 额度足够时记录一笔用量，否则不记录并返回需要等待的时间
+    async fn try_record(&self, key: &str, amount: u64, limit: u64, window: Duration) -> Result<Result<(), Duration>>;
+
+    /// 还要等待多久才能再使用 `amount` 的额度，`amount` 超过 `limit` 时为整个窗口
+    async fn retry_after(&self, key: &str, amount: u64, limit: u64, window: Duration) -> Result<Duration>;
+}
+
+/// 按配置创建限流后端（启动时调用一次）
+pub fn create_rate_limit_backend(config: &RateLimitBackendConfig) -> Result<Box<dyn RateLimitBackend>> {
+    Ok(match config {
+        RateLimitBackendConfig::Memory => Box::new(memory::MemoryRateLimiter::default()),
+        RateLimitBackendConfig::Redis(redis_config) => Box::new(redis::RedisRateLimiter::new(redis_config)?),
+    })
+}
+
+/// 登录、回调与上传共用的限流器
+///
+/// 后端不可用时放行请求并记录警告：限流暂时失效，比拒绝所有请求的影响小
+pub struct RateLimiter {
+    backend: Box<dyn RateLimitBackend>,
+}
+
+impl RateLimiter {
+    pub fn new(backend: Box<dyn RateLimitBackend>) -> Self {
+        Self { backend }
+    }
+
+    /// 窗口内还能使用的额度，后端不可用时视为没有使用
+    pub async fn remaining(&self, key: &str, limit: u64, window: Duration) -> u64 {
+        self.backend.remaining(key, limit, window).await.unwrap_or_else(|e| {
+            warn!("限流后端不可用，{} 不限流: {:?}", key, e);
+            limit
+        })
+    }
+
+    /// 记录一笔用量
+    pub async fn record(&self, key: &str, amount: u64, window: Duration) {
+        if let Err(e) = self.backend.record(key, amount, window).await {
+            warn!("限流后端不可用，{} 的用量未记录: {:?}", key, e);
+        }
+    }
+
+    /// 额度足够时记录一笔用量，否则返回需要等待的时间
+    pub async fn try_record(&self, key: &str, amount: u64, limit: u64, window: Duration) -> Result<(), Duration> {
+        self.backend.try_record(key, amount, limit, window).await.unwrap_or_else(|e| {
+            warn!("限流后端不可用，{} 不限流: {:?}", key, e);
+            Ok(())
+        })
+    }
+
+    /// 还要等待多久才能再使用 `amount` 的额度，后端不可用时为整个窗口
+    pub async fn retry_after(&self, key: &str, amount: u64, limit: u64, window: Duration) -> Duration {
+        self.backend.retry_after(key, amount, limit, window).await.unwrap_or_else(|e| {
+            warn!("限流后端不可用: {:?}", e);
+            window
+        })
+    }
+}
@@ -0,0 +1,180 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use redis::{Client, Script, aio::{ConnectionManager, ConnectionManagerConfig}};
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use uuid::Uuid;
+
+use super::RateLimitBackend;
+use crate::config::RedisRateLimitConfig;
+
+/// 连接与单次命令的超时时间，Redis 不可用时请求不会长时间挂起
+const REDIS_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 在 Redis 中原子地完成清理、统计与记录
+///
+/// 每个 key 是一个有序集合，score 为记录时间（毫秒），成员为 `{用量}:{唯一 ID}`；
+/// 时间取 Redis 服务器的时钟，各实例之间的时钟偏差不影响窗口。
+/// 返回值：`remaining` 为剩余额度；`try` 与 `wait` 为需要等待的毫秒数（0 表示额度足够，`try` 同时记录）
+const SLIDING_WINDOW_SCRIPT: &str = r"
+local key = KEYS[1]
+local op = ARGV[1]
+local window = tonumber(ARGV[2])
+local amount = tonumber(ARGV[3])
+local limit = tonumber(ARGV[4])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+redis.call('ZREMRANGEBYSCORE', key, '-inf', now - window)
+
+local function add()
+  redis.call('ZADD', key, now, ARGV[3] .. ':' .. ARGV[5])
+  redis.call('PEXPIRE', key, window)
+end
+
+if op == 'record' then
+  add()
+  return 0
+end
+
+local entries = redis.call('ZRANGE', key, 0, -1, 'WITHSCORES')
+local used = 0
+for i = 1, #entries, 2 do
+  used = used + tonumber(string.match(entries[i], '^(%d+):'))
+end
+if op == 'remaining' then
+  return math.max(limit - used, 0)
+end
+if used + amount <= limit then
+  if op == 'try' then
+    add()
+  end
+  return 0
+end
+for i = 1, #entries, 2 do
+  used = used - tonumber(string.match(entries[i], '^(%d+):'))
+  if used + amount <= limit then
+    return math.max(tonumber(entries[i + 1]) + window - now, 1)
+  end
+end
+return window
+";
+
+/// 保存在 Redis 中的限流计数，多个实例共享同一份计数
+pub struct RedisRateLimiter {
+    client: Client,
+    /// 首次使用时建立连接，之后由 `ConnectionManager` 自动重连
+    connection: OnceCell<ConnectionManager>,
+    key_prefix: String,
+    script: Script,
+}
+
+impl RedisRateLimiter {
+    pub fn new(config: &RedisRateLimitConfig) -> Result<Self> {
+        Ok(Self {
+            client: Client::open(config.url.as_str())?,
+            connection: OnceCell::new(),
+            key_prefix: config.key_prefix.clone(),
+            script: Script::new(SLIDING_WINDOW_SCRIPT),
+        })
+    }
+
+    async fn invoke(&self, op: &str, key: &str, amount: u64, limit: u64, window: Duration) -> Result<u64> {
+        let connection = self.connection.get_or_try_init(|| {
+            let config = ConnectionManagerConfig::new()
+                .set_connection_timeout(REDIS_TIMEOUT)
+                .set_response_timeout(REDIS_TIMEOUT)
+                .set_number_of_retries(1);
+            ConnectionManager::new_with_config(self.client.clone(), config)
+        }).await?;
+        let value = self.script
+            .key(format!("{}{}", self.key_prefix, key))
+            .arg(op)
+            .arg(window.as_millis() as u64)
+            .arg(amount)
+            .arg(limit)
+            .arg(Uuid::new_v4().simple().to_string())
+            .invoke_async(&mut connection.clone())
+            .await?;
+        Ok(value)
+    }
+}
+
+#[async_trait]
+impl RateLimitBackend for RedisRateLimiter {
+    async fn remaining(&self, key: &str, limit: u64, window: Duration) -> Result<u64> {
+        self.invoke("remaining", key, 0, limit, window).await
+    }
+
+    async fn record(&self, key: &str, amount: u64, window: Duration) -> Result<()> {
+        self.invoke("record", key, amount, 0, window).await?;
+        Ok(())
+    }
+
+    async fn try_record(&self, key: &str, amount: u64, limit: u64, window: Duration) -> Result<Result<(), Duration>> {
+        match self.invoke("try", key, amount, limit, window).await? {
+            0 => Ok(Ok(())),
+            wait => Ok(Err(Duration::from_millis(wait))),
+        }
+    }
+
+    async fn retry_after(&self, key: &str, amount: u64, limit: u64, window: Duration) -> Result<Duration> {
+        self.invoke("wait", key, amount, limit, window).await.map(Duration::from_millis)
+    }
+}
+
+/// 需要真实的 Redis：设置 `REDIS_URL`（如 `redis://127.0.0.1:6379/0`）后运行，未设置时跳过
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    /// 每个测试使用独立的 key 前缀，互不干扰
+    fn limiter() -> Option<RedisRateLimiter> {
+        let url = std::env::var("REDIS_URL").ok()?;
+        let config = RedisRateLimitConfig {
+            url,
+            key_prefix: format!("ysm_upload:test:{}:", Uuid::new_v4().simple()),
+        };
+        Some(RedisRateLimiter::new(&config).expect("REDIS_URL should be a valid Redis URL"))
+    }
+
+    #[tokio::test]
+    async fn try_record_rejects_over_limit() {
+        let Some(limiter) = limiter() else {
+            return;
+        };
+        assert!(limiter.try_record("alice", 2, 3, MINUTE).await.unwrap().is_ok());
+        let after = limiter.try_record("alice", 2, 3, MINUTE).await.unwrap().unwrap_err();
+        assert!(after > Duration::ZERO && after <= MINUTE);
+        // 被拒绝的请求不计入用量
+        assert_eq!(limiter.remaining("alice", 3, MINUTE).await.unwrap(), 1);
+        assert_eq!(limiter.remaining("bob", 3, MINUTE).await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn retry_after_waits_for_oldest_usage() {
+        let Some(limiter) = limiter() else {
+            return;
+        };
+        limiter.record("alice", 2, MINUTE).await.unwrap();
+        limiter.record("alice", 1, MINUTE).await.unwrap();
+        assert_eq!(limiter.retry_after("alice", 0, 3, MINUTE).await.unwrap(), Duration::ZERO);
+        let after = limiter.retry_after("alice", 2, 3, MINUTE).await.unwrap();
+        assert!(after > Duration::ZERO && after <= MINUTE);
+        assert_eq!(limiter.retry_after("alice", 4, 3, MINUTE).await.unwrap(), MINUTE);
+    }
+
+    #[tokio::test]
+    async fn usage_expires_after_window() {
+        let Some(limiter) = limiter() else {
+            return;
+        };
+        let window = Duration::from_millis(200);
+        assert!(limiter.try_record("alice", 1, 1, window).await.unwrap().is_ok());
+        assert!(limiter.try_record("alice", 1, 1, window).await.unwrap().is_err());
+        tokio::time::sleep(window + Duration::from_millis(50)).await;
+        assert_eq!(limiter.remaining("alice", 1, window).await.unwrap(), 1);
+        assert!(limiter.try_record("alice", 1, 1, window).await.unwrap().is_ok());
+    }
+}
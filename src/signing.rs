@@ -0,0 +1,156 @@
+use hmac::{Hmac, digest::KeyInit};
+use jwt::{
+    AlgorithmType, Error, Header, SignWithStore, SigningAlgorithm, Token, VerifyWithKey, VerifyWithStore,
+    VerifyingAlgorithm,
+};
+use serde::{Serialize, de::DeserializeOwned};
+use sha2::{Sha256, Sha384, Sha512};
+use std::collections::BTreeMap;
+
+use crate::config::{OAuthProvidersConfig, SigningAlgorithmKind};
+
+/// 由 `secret_string` 派生的密钥（HS256）使用的 kid
+///
+/// 即使配置了 `signing_keys` 也始终可以用于验证，轮换密钥前签发的 token 不会失效；
+/// `signing_keys` 中定义了同名密钥时以配置为准
+pub const DEFAULT_KID: &str = "default";
+
+/// 单个签名密钥
+enum SigningKey {
+    Hs256(Hmac<Sha256>),
+    Hs384(Hmac<Sha384>),
+    Hs512(Hmac<Sha512>),
+}
+
+impl SigningKey {
+    fn new(algorithm: SigningAlgorithmKind, secret: &[u8]) -> Self {
+        const MSG: &str = "HMAC can take key of any size";
+        match algorithm {
+            SigningAlgorithmKind::Hs256 => Self::Hs256(Hmac::new_from_slice(secret).expect(MSG)),
+            SigningAlgorithmKind::Hs384 => Self::Hs384(Hmac::new_from_slice(secret).expect(MSG)),
+            SigningAlgorithmKind::Hs512 => Self::Hs512(Hmac::new_from_slice(secret).expect(MSG)),
+        }
+    }
+}
+
+impl SigningAlgorithm for SigningKey {
+    fn algorithm_type(&self) -> AlgorithmType {
+        match self {
+            Self::Hs256(key) => SigningAlgorithm::algorithm_type(key),
+            Self::Hs384(key) => SigningAlgorithm::algorithm_type(key),
+            Self::Hs512(key) => SigningAlgorithm::algorithm_type(key),
+        }
+    }
+
+    fn sign(&self, header: &str, claims: &str) -> Result<String, Error> {
+        match self {
+            Self::Hs256(key) => key.sign(header, claims),
+            Self::Hs384(key) => key.sign(header, claims),
+            Self::Hs512(key) => key.sign(header, claims),
+        }
+    }
+}
+
+impl VerifyingAlgorithm for SigningKey {
+    fn algorithm_type(&self) -> AlgorithmType {
+        SigningAlgorithm::algorithm_type(self)
+    }
+
+    fn verify_bytes(&self, header: &str, claims: &str, signature: &[u8]) -> Result<bool, Error> {
+        match self {
+            Self::Hs256(key) => key.verify_bytes(header, claims, signature),
+            Self::Hs384(key) => key.verify_bytes(header, claims, signature),
+            Self::Hs512(key) => key.verify_bytes(header, claims, signature),
+        }
+    }
+}
+
+/// 会话、state 等签名 token 使用的密钥集合
+///
+/// 签名时使用当前密钥，并把它的 kid 写入 JWT 头；验证时按 kid 选择密钥和算法，
+/// 因此可以在新旧密钥（包括不同算法）之间平滑过渡。
+/// 没有 kid 的旧 token 按 HS256 + `secret_string` 验证。
+pub struct Signer {
+    current_kid: String,
+    keys: BTreeMap<String, SigningKey>,
+    legacy: Hmac<Sha256>,
+}
+
+impl Signer {
+    /// 根据配置构建密钥集合，配置需已通过 `Config::validate` 校验
+    pub fn new(config: &OAuthProvidersConfig) -> Self {
+        let legacy = Hmac::new_from_slice(config.secret_string.as_bytes())
+            .expect("HMAC can take key of any size");
+
+        let mut keys = BTreeMap::new();
+        keys.insert(
+            DEFAULT_KID.to_string(),
+            SigningKey::new(SigningAlgorithmKind::Hs256, config.secret_string.as_bytes()),
+        );
+        let current_kid = config.current_kid.clone().unwrap_or_else(|| DEFAULT_KID.to_string());
+        for key in &config.signing_keys {
+            keys.insert(key.kid.clone(), SigningKey::new(key.algorithm, key.secret.as_bytes()));
+        }
+
+        Self { current_kid, keys, legacy }
+    }
+
+    /// 使用当前密钥签名
+    pub fn sign<C: Serialize>(&self, claims: &C) -> Result<String, Error> {
+        (self.current_kid.as_str(), claims).sign_with_store(&self.keys)
+    }
+
+    /// 按 token 头中的 kid 选择密钥验证
+    pub fn verify<C: DeserializeOwned>(&self, token: &str) -> Result<C, Error> {
+        let unverified: Token<Header, C, _> = Token::parse_unverified(token)?;
+        let verified = if unverified.header().key_id.is_some() {
+            unverified.verify_with_store(&self.keys)?
+        } else {
+            unverified.verify_with_key(&self.legacy)?
+        };
+        let (_, claims) = verified.into();
+        Ok(claims)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oauth_config(extra: &str) -> OAuthProvidersConfig {
+        let yaml = format!("prefix_url: http://127.0.0.1:3000\nsecret_string: test-secret\nproviders: {{}}\n{}", extra);
+        serde_yaml::from_str(&yaml).expect("test config should parse")
+    }
+
+    const ROTATED: &str = "\
+signing_keys:
+- kid: k2
+  algorithm: HS512
+  secret: new-secret
+current_kid: k2
+";
+
+    #[test]
+    fn token_signed_before_rotation_still_verifies() {
+        let token = Signer::new(&oauth_config("")).sign(&"claims").unwrap();
+
+        let rotated = Signer::new(&oauth_config(ROTATED));
+        assert_eq!(rotated.verify::<String>(&token).unwrap(), "claims");
+    }
+
+    #[test]
+    fn rotated_signer_signs_with_current_kid() {
+        let rotated = Signer::new(&oauth_config(ROTATED));
+        let token = rotated.sign(&"claims").unwrap();
+        let unverified: Token<Header, String, _> = Token::parse_unverified(&token).unwrap();
+        assert_eq!(unverified.header().key_id.as_deref(), Some("k2"));
+        assert_eq!(rotated.verify::<String>(&token).unwrap(), "claims");
+    }
+
+    #[test]
+    fn configured_default_kid_replaces_secret_string() {
+        let before = Signer::new(&oauth_config("")).sign(&"claims").unwrap();
+        let overridden = Signer::new(&oauth_config("signing_keys:\n- kid: default\n  secret: other-secret\ncurrent_kid: default\n"));
+        assert!(overridden.verify::<String>(&before).is_err());
+    }
+}
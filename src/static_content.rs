@@ -1,16 +1,160 @@
 use axum::{
+    body::Bytes,
+    extract::State,
     response::{IntoResponse, Response},
-    http::{StatusCode, header},
+    http::{HeaderMap, StatusCode, header},
 };
-use rust_embed::RustEmbed;
+use rust_embed::{EmbeddedFile, RustEmbed};
+use std::{borrow::Cow, path::{Component, Path}, sync::Arc};
+
+use crate::AppState;
+
+/// 不走 SPA 回退的路径前缀
+const WELL_KNOWN_PREFIX: &str = ".well-known/";
 
 #[derive(RustEmbed)]
 #[folder = "frontend/dist"]
 struct Assets;
 
-pub async fn serve_static(uri: axum::http::Uri) -> Response {
+/// 请求的字节范围
+enum ByteRange {
+    /// 返回完整内容
+    Full,
+    /// 返回 [start, end] 闭区间
+    Partial(u64, u64),
+    /// 范围无法满足
+    Unsatisfiable,
+}
+
+/// 解析 `Range` 请求头，只支持单个字节范围
+///
+/// 格式无法识别或包含多个范围时退回到完整内容
+fn parse_range(value: &str, len: u64) -> ByteRange {
+    let Some(spec) = value.trim().strip_prefix("bytes=") else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+
+    let (start, end) = match (start.trim(), end.trim()) {
+        // bytes=-N：最后 N 个字节
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => return ByteRange::Unsatisfiable,
+            Ok(n) => (len.saturating_sub(n), len.saturating_sub(1)),
+            Err(_) => return ByteRange::Full,
+        },
+        // bytes=N- 或 bytes=N-M
+        (start, end) => {
+            let Ok(start) = start.parse::<u64>() else {
+                return ByteRange::Full;
+            };
+            let end = if end.is_empty() {
+                len.saturating_sub(1)
+            } else {
+                match end.parse::<u64>() {
+                    Ok(end) if end >= start => end.min(len.saturating_sub(1)),
+                    _ => return ByteRange::Full,
+                }
+            };
+            (start, end)
+        }
+    };
+
+    if len == 0 || start >= len {
+        ByteRange::Unsatisfiable
+    } else {
+        ByteRange::Partial(start, end)
+    }
+}
+
+/// 构造静态文件响应，支持 Range / If-Range
+fn respond(content: EmbeddedFile, mime: &str, headers: &HeaderMap) -> Response {
+    let etag = format!(
+        "\"{}\"",
+        content.metadata.sha256_hash().iter().map(|b| format!("{:02x}", b)).collect::<String>()
+    );
+    let data = match content.data {
+        Cow::Borrowed(data) => Bytes::from_static(data),
+        Cow::Owned(data) => Bytes::from(data),
+    };
+    let len = data.len() as u64;
+
+    // If-Range 与当前 ETag 不一致时忽略 Range，返回完整内容
+    let if_range_matches = headers
+        .get(header::IF_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .is_none_or(|value| value == etag);
+    let range = match headers.get(header::RANGE).and_then(|value| value.to_str().ok()) {
+        Some(value) if if_range_matches => parse_range(value, len),
+        _ => ByteRange::Full,
+    };
+
+    match range {
+        ByteRange::Full => (
+            StatusCode::OK,
+            [
+                (header::CONTENT_TYPE, mime.to_string()),
+                (header::ACCEPT_RANGES, "bytes".to_string()),
+                (header::ETAG, etag),
+            ],
+            data,
+        ).into_response(),
+        ByteRange::Partial(start, end) => (
+            StatusCode::PARTIAL_CONTENT,
+            [
+                (header::CONTENT_TYPE, mime.to_string()),
+                (header::ACCEPT_RANGES, "bytes".to_string()),
+                (header::ETAG, etag),
+                (header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, len)),
+            ],
+            data.slice(start as usize..=end as usize),
+        ).into_response(),
+        ByteRange::Unsatisfiable => (
+            StatusCode::RANGE_NOT_SATISFIABLE,
+            [(header::CONTENT_RANGE, format!("bytes */{}", len))],
+        ).into_response(),
+    }
+}
+
+/// 从配置的目录读取 `/.well-known/` 下的文件
+///
+/// 只接受普通的路径分量，拒绝 `..` 等可能逃出目录的路径
+async fn read_well_known(dir: &Path, path: &str) -> Option<Vec<u8>> {
+    let relative = Path::new(path);
+    if !relative.components().all(|component| matches!(component, Component::Normal(_))) {
+        return None;
+    }
+    tokio::fs::read(dir.join(relative)).await.ok()
+}
+
+pub async fn serve_static(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    uri: axum::http::Uri,
+) -> Response {
     let path = uri.path().trim_start_matches('/');
-    
+
+    // `.well-known` 下的文件优先从磁盘目录读取，找不到时返回真正的 404 而不是 index.html
+    if let Some(well_known_path) = path.strip_prefix(WELL_KNOWN_PREFIX) {
+        if let Some(dir) = &state.config().server.well_known_dir
+            && let Some(data) = read_well_known(dir, well_known_path).await
+        {
+            let mime = mime_guess::from_path(path).first_or_octet_stream();
+            return ([(header::CONTENT_TYPE, mime.to_string())], data).into_response();
+        }
+        return match Assets::get(path) {
+            Some(content) => {
+                let mime = mime_guess::from_path(path).first_or_octet_stream();
+                respond(content, mime.as_ref(), &headers)
+            }
+            None => (StatusCode::NOT_FOUND, "404 Not Found").into_response(),
+        };
+    }
+
     // 如果路径为空，返回 index.html
     let path = if path.is_empty() || path == "/" {
         "index.html"
@@ -21,26 +165,16 @@ pub async fn serve_static(uri: axum::http::Uri) -> Response {
     match Assets::get(path) {
         Some(content) => {
             let mime = mime_guess::from_path(path).first_or_octet_stream();
-            (
-                StatusCode::OK,
-                [(header::CONTENT_TYPE, mime.as_ref())],
-                content.data,
-            ).into_response()
+            respond(content, mime.as_ref(), &headers)
         }
         None => {
             // 如果文件不存在，返回 index.html (用于 SPA 路由)
             match Assets::get("index.html") {
-                Some(content) => {
-                    (
-                        StatusCode::OK,
-                        [(header::CONTENT_TYPE, "text/html")],
-                        content.data,
-                    ).into_response()
-                }
+                Some(content) => respond(content, "text/html", &headers),
                 None => {
                     (StatusCode::NOT_FOUND, "404 Not Found").into_response()
                 }
             }
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,106 @@
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use axum::body::{Body, Bytes};
+use std::{io::ErrorKind, path::{Component, Path, PathBuf}};
+use tokio::fs;
+use tokio_util::io::ReaderStream;
+
+use super::{StorageBackend, StoredObject, object_key};
+
+/// 保存在本地目录中的存储后端
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// key 对应的文件路径，拒绝 `..` 等可能逃出目录的 key
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        let relative = Path::new(key);
+        if !relative.components().all(|component| matches!(component, Component::Normal(_))) {
+            bail!("invalid storage key: {}", key);
+        }
+        Ok(self.root.join(relative))
+    }
+}
+
+#[async_trait]
+impl StorageBackend for LocalStorage {
+    async fn put(&self, owner: &str, name: &str, data: Bytes) -> Result<String> {
+        let key = object_key(owner, name);
+        let path = self.path(&key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(&path, &data).await?;
+        Ok(key)
+    }
+
+    async fn put_file(&self, owner: &str, name: &str, path: &Path, _storage_class: Option<&str>) -> Result<String> {
+        let key = object_key(owner, name);
+        let dest = self.path(&key)?;
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        // 在原文件旁建立硬链接再替换目标，不复制数据；不在同一文件系统时退回到复制
+        let staging = path.with_extension("link");
+        if fs::hard_link(path, &staging).await.is_err() {
+            fs::copy(path, &staging).await?;
+        }
+        if let Err(e) = fs::rename(&staging, &dest).await {
+            let _ = fs::remove_file(&staging).await;
+            return Err(e.into());
+        }
+        Ok(key)
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Bytes>> {
+        match fs::read(self.path(key)?).await {
+            Ok(data) => Ok(Some(Bytes::from(data))),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn get_stream(&self, key: &str) -> Result<Option<Body>> {
+        match fs::File::open(self.path(key)?).await {
+            Ok(file) => Ok(Some(Body::from_stream(ReaderStream::new(file)))),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool> {
+        Ok(fs::try_exists(self.path(key)?).await?)
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        match fs::remove_file(self.path(key)?).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn list(&self, owner: &str) -> Result<Vec<StoredObject>> {
+        let mut entries = match fs::read_dir(self.path(owner)?).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut objects = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            if metadata.is_file() {
+                objects.push(StoredObject {
+                    key: object_key(owner, &entry.file_name().to_string_lossy()),
+                    size: metadata.len(),
+                });
+            }
+        }
+        Ok(objects)
+    }
+}
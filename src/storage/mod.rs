@@ -0,0 +1,92 @@
+pub mod local;
+pub mod s3;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use axum::body::{Body, Bytes};
+use serde::Serialize;
+use std::{path::Path, sync::Arc};
+
+use crate::config::{ServerConfig, StorageConfig};
+
+/// 已保存的对象
+#[derive(Debug, Clone, Serialize)]
+pub struct StoredObject {
+    /// 对象的完整 key（`{owner}/{name}`）
+    pub key: String,
+    pub size: u64,
+}
+
+/// 上传文件的存储后端
+///
+/// 对象按用户目录分组，key 的格式为 `{owner}/{name}`
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+    /// 保存文件，返回对象的完整 key
+    async fn put(&self, owner: &str, name: &str, data: Bytes) -> Result<String>;
+
+    /// 按指定的存储类别保存文件内容，`None` 时使用后端配置的默认类别
+    ///
+    /// 不区分存储类别的后端直接调用 [`put`](Self::put)
+    async fn put_with_class(&self, owner: &str, name: &str, data: Bytes, _storage_class: Option<&str>) -> Result<String> {
+        self.put(owner, name, data).await
+    }
+
+    /// 按指定的存储类别保存本地文件的内容，`path` 处的文件保持不变，由调用方删除
+    ///
+    /// 默认读入内存后调用 [`put_with_class`](Self::put_with_class)
+    async fn put_file(&self, owner: &str, name: &str, path: &Path, storage_class: Option<&str>) -> Result<String> {
+        let data = Bytes::from(tokio::fs::read(path).await?);
+        self.put_with_class(owner, name, data, storage_class).await
+    }
+
+    /// 允许客户端选择的存储类别，为空时不允许选择
+    fn storage_classes(&self) -> &[String] {
+        &[]
+    }
+
+    /// 已保存对象实际的存储类别，标准类别或后端不区分存储类别时返回 `None`
+    async fn storage_class(&self, _key: &str) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// 读取文件，不存在时返回 `None`
+    async fn get(&self, key: &str) -> Result<Option<Bytes>>;
+
+    /// 以流的形式读取文件，不存在时返回 `None`
+    ///
+    /// 默认读入内存后返回，大文件应由后端逐块读取
+    async fn get_stream(&self, key: &str) -> Result<Option<Body>> {
+        Ok(self.get(key).await?.map(Body::from))
+    }
+
+    /// 文件是否存在
+    async fn exists(&self, key: &str) -> Result<bool>;
+
+    /// 删除文件，不存在时视为成功
+    async fn delete(&self, key: &str) -> Result<()>;
+
+    /// 列出用户的所有文件
+    async fn list(&self, owner: &str) -> Result<Vec<StoredObject>>;
+
+    /// 客户端可以直接访问的下载地址（如 S3 预签名 URL）
+    ///
+    /// 下载时的响应应带上 `content_type` 与 `content_disposition`，与 `/api/files/{owner}/{name}` 一致。
+    /// 返回 `None` 时由 `/api/files/{owner}/{name}` 提供下载
+    async fn download_url(&self, _key: &str, _content_type: &str, _content_disposition: &str) -> Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+/// 按配置创建存储后端（启动时调用一次）
+pub fn create_storage_backend(config: &ServerConfig) -> Arc<dyn StorageBackend> {
+    match &config.storage {
+        StorageConfig::Local => Arc::new(local::LocalStorage::new(config.upload_dir.clone())),
+        StorageConfig::S3(s3_config) => Arc::new(s3::S3Storage::new(s3_config)),
+    }
+}
+
+/// 拼接对象 key
+fn object_key(owner: &str, name: &str) -> String {
+    format!("{}/{}", owner, name)
+}
@@ -0,0 +1,192 @@
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use aws_sdk_s3::{
+    Client,
+    config::{BehaviorVersion, Credentials, Region},
+    error::DisplayErrorContext,
+    presigning::PresigningConfig,
+    primitives::ByteStream,
+    types::StorageClass,
+};
+use std::{path::Path, time::Duration};
+use aws_smithy_http_client::tls;
+use axum::body::{Body, Bytes};
+use tokio_util::io::ReaderStream;
+
+use super::{StorageBackend, StoredObject, object_key};
+use crate::config::S3StorageConfig;
+
+/// S3 兼容对象存储（AWS S3、MinIO 等）
+pub struct S3Storage {
+    client: Client,
+    bucket: String,
+    /// 预签名下载地址的有效期，未设置时不生成
+    presign_ttl: Option<Duration>,
+    /// 上传内容默认使用的存储类别
+    storage_class: Option<String>,
+    allowed_storage_classes: Vec<String>,
+}
+
+impl S3Storage {
+    pub fn new(config: &S3StorageConfig) -> Self {
+        // 与其它出站请求一样使用 ring 作为 rustls 的加密实现
+        let http_client = aws_smithy_http_client::Builder::new()
+            .tls_provider(tls::Provider::Rustls(tls::rustls_provider::CryptoMode::Ring))
+            .build_https();
+        let mut builder = aws_sdk_s3::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .http_client(http_client)
+            .region(Region::new(config.region.clone()))
+            .credentials_provider(Credentials::new(
+                &config.access_key_id,
+                &config.secret_access_key,
+                None,
+                None,
+                "config",
+            ))
+            .force_path_style(config.force_path_style);
+        if let Some(endpoint) = &config.endpoint {
+            builder = builder.endpoint_url(endpoint);
+        }
+
+        Self {
+            client: Client::from_conf(builder.build()),
+            bucket: config.bucket.clone(),
+            presign_ttl: config.presign_ttl_secs.map(Duration::from_secs),
+            storage_class: config.storage_class.clone(),
+            allowed_storage_classes: config.allowed_storage_classes.clone(),
+        }
+    }
+}
+
+/// 保留 SDK 错误的完整原因链
+fn sdk_error(e: impl std::error::Error) -> anyhow::Error {
+    anyhow!("{}", DisplayErrorContext(e))
+}
+
+#[async_trait]
+impl StorageBackend for S3Storage {
+    async fn put(&self, owner: &str, name: &str, data: Bytes) -> Result<String> {
+        let key = object_key(owner, name);
+        self.client.put_object()
+            .bucket(&self.bucket)
+            .key(&key)
+            .body(ByteStream::from(data))
+            .send().await
+            .map_err(sdk_error)?;
+        Ok(key)
+    }
+
+    async fn put_with_class(&self, owner: &str, name: &str, data: Bytes, storage_class: Option<&str>) -> Result<String> {
+        let key = object_key(owner, name);
+        let storage_class = storage_class.or(self.storage_class.as_deref());
+        self.client.put_object()
+            .bucket(&self.bucket)
+            .key(&key)
+            .body(ByteStream::from(data))
+            .set_storage_class(storage_class.map(StorageClass::from))
+            .send().await
+            .map_err(sdk_error)?;
+        Ok(key)
+    }
+
+    async fn put_file(&self, owner: &str, name: &str, path: &Path, storage_class: Option<&str>) -> Result<String> {
+        let key = object_key(owner, name);
+        let storage_class = storage_class.or(self.storage_class.as_deref());
+        self.client.put_object()
+            .bucket(&self.bucket)
+            .key(&key)
+            .body(ByteStream::from_path(path).await?)
+            .set_storage_class(storage_class.map(StorageClass::from))
+            .send().await
+            .map_err(sdk_error)?;
+        Ok(key)
+    }
+
+    fn storage_classes(&self) -> &[String] {
+        &self.allowed_storage_classes
+    }
+
+    async fn storage_class(&self, key: &str) -> Result<Option<String>> {
+        // S3 只在非 STANDARD 类别时返回 x-amz-storage-class
+        let output = self.client.head_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send().await
+            .map_err(sdk_error)?;
+        Ok(output.storage_class().map(|class| class.as_str().to_string()))
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Bytes>> {
+        let output = match self.client.get_object().bucket(&self.bucket).key(key).send().await {
+            Ok(output) => output,
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_no_such_key()) => return Ok(None),
+            Err(e) => return Err(sdk_error(e)),
+        };
+        let data = output.body.collect().await?.into_bytes();
+        Ok(Some(data))
+    }
+
+    async fn get_stream(&self, key: &str) -> Result<Option<Body>> {
+        let output = match self.client.get_object().bucket(&self.bucket).key(key).send().await {
+            Ok(output) => output,
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_no_such_key()) => return Ok(None),
+            Err(e) => return Err(sdk_error(e)),
+        };
+        Ok(Some(Body::from_stream(ReaderStream::new(output.body.into_async_read()))))
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool> {
+        match self.client.head_object().bucket(&self.bucket).key(key).send().await {
+            Ok(_) => Ok(true),
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_not_found()) => Ok(false),
+            Err(e) => Err(sdk_error(e)),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.client.delete_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send().await
+            .map_err(sdk_error)?;
+        Ok(())
+    }
+
+    async fn list(&self, owner: &str) -> Result<Vec<StoredObject>> {
+        let mut pages = self.client.list_objects_v2()
+            .bucket(&self.bucket)
+            .prefix(format!("{}/", owner))
+            .into_paginator()
+            .send();
+
+        let mut objects = Vec::new();
+        while let Some(page) = pages.next().await {
+            for object in page.map_err(sdk_error)?.contents() {
+                if let Some(key) = object.key() {
+                    objects.push(StoredObject {
+                        key: key.to_string(),
+                        size: object.size().unwrap_or(0).max(0) as u64,
+                    });
+                }
+            }
+        }
+        Ok(objects)
+    }
+
+    async fn download_url(&self, key: &str, content_type: &str, content_disposition: &str) -> Result<Option<String>> {
+        let Some(ttl) = self.presign_ttl else {
+            return Ok(None);
+        };
+        // 内容按哈希保存，没有类型与文件名，由签名的查询参数覆盖响应头
+        let request = self.client.get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .response_content_type(content_type)
+            .response_content_disposition(content_disposition)
+            .presigned(PresigningConfig::expires_in(ttl)?)
+            .await
+            .map_err(sdk_error)?;
+        Ok(Some(request.uri().to_string()))
+    }
+}
@@ -0,0 +1,76 @@
+use axum::{
+    extract::{Request, State},
+    http::Uri,
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+};
+
+use crate::{config::TrailingSlash, error::ApiError};
+
+/// 统一处理路径末尾的斜杠
+///
+/// 该中间件包裹在整个 Router 外层，在路由匹配之前执行：
+/// - `strip`：透明地去掉末尾斜杠后再路由，客户端无感知
+/// - `redirect`：以 308 重定向到不带斜杠的规范地址（保留请求方法与查询参数）
+/// - `strict`：不做处理，`/api/user/` 与 `/api/user` 视为不同路由
+pub async fn normalize_trailing_slash(
+    State(mode): State<TrailingSlash>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path();
+    if mode == TrailingSlash::Strict || path == "/" || !path.ends_with('/') {
+        return next.run(request).await;
+    }
+
+    // 开头连续的斜杠合并为一个：`//evil.com/` 去掉末尾斜杠后的 `//evil.com`
+    // 会被浏览器当作指向其它站点的协议相对地址，造成开放重定向
+    let trimmed = format!("/{}", path.trim_start_matches(['/', '\\']).trim_end_matches('/'));
+    let path_and_query = match request.uri().query() {
+        Some(query) => format!("{}?{}", trimmed, query),
+        None => trimmed,
+    };
+
+    if mode == TrailingSlash::Redirect {
+        return Redirect::permanent(&path_and_query).into_response();
+    }
+
+    let mut parts = request.uri().clone().into_parts();
+    parts.path_and_query = match path_and_query.parse() {
+        Ok(path_and_query) => Some(path_and_query),
+        Err(_) => return ApiError::bad_request("Invalid request path").into_response(),
+    };
+    match Uri::from_parts(parts) {
+        Ok(uri) => *request.uri_mut() = uri,
+        Err(_) => return ApiError::bad_request("Invalid request path").into_response(),
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, body::Body, http::{StatusCode, header}, middleware, routing::get};
+    use tower::ServiceExt;
+
+    async fn redirect_location(path: &str) -> String {
+        let app = Router::new()
+            .route("/api/user", get(|| async { "ok" }))
+            .layer(middleware::from_fn_with_state(TrailingSlash::Redirect, normalize_trailing_slash));
+        let request = Request::builder().uri(path).body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        response.headers()[header::LOCATION].to_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn redirect_drops_trailing_slash_and_keeps_query() {
+        assert_eq!(redirect_location("/api/user/?a=1").await, "/api/user?a=1");
+    }
+
+    #[tokio::test]
+    async fn redirect_never_points_to_another_host() {
+        assert_eq!(redirect_location("//evil.com/").await, "/evil.com");
+        assert_eq!(redirect_location("///evil.com//").await, "/evil.com");
+    }
+}
@@ -0,0 +1,790 @@
+use axum::{
+    body::Bytes,
+    extract::{Multipart, Path, Query, State, multipart::{Field, MultipartError}},
+    http::{HeaderMap, StatusCode, header},
+    response::IntoResponse,
+    Json,
+};
+use futures::{StreamExt, TryStreamExt, stream};
+use serde::{Deserialize, Serialize};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use sha2::{Digest, Sha256};
+use std::{path::PathBuf, sync::Arc, time::{Duration, SystemTime}};
+use tokio::{fs, io::{AsyncWriteExt, BufWriter}, sync::Mutex};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{AppState, error::ApiError, oauth::UnifiedUserInfo, rate_limit::RateLimiter};
+
+/// 保留的原始扩展名的最大长度
+const MAX_EXTENSION_LEN: usize = 16;
+/// 检查文件头时读取的字节数
+const SNIFF_LEN: usize = 8;
+/// 按内容去重的数据保存在 `.blobs/{sha256}`；用户目录名中不会出现 `.`，因此不会冲突
+const BLOB_PREFIX: &str = ".blobs";
+/// 文件 ID 到所属用户目录的索引保存在 `.owners/{file_id}`，删除时用于区分 403 与 404
+const OWNER_PREFIX: &str = ".owners";
+/// 内容的引用记录保存在 `.refs/{sha256}/{file_id}`，没有引用时删除内容
+const REF_PREFIX: &str = ".refs";
+/// 上传中的文件暂存在 `{upload_dir}/.tmp`，写完并计算出哈希后再保存到 `.blobs`
+const TEMP_PREFIX: &str = ".tmp";
+/// 保存的原始文件名的最大长度（字符）
+const MAX_FILENAME_LEN: usize = 255;
+/// 文件列表每页的默认数量与上限
+const DEFAULT_PAGE_LIMIT: u32 = 10;
+const MAX_PAGE_LIMIT: u32 = 100;
+/// 同时读取文件记录的数量
+const POINTER_READ_CONCURRENCY: usize = 8;
+/// 客户端选择存储类别的请求头
+pub const STORAGE_CLASS_HEADER: &str = "X-Storage-Class";
+/// 上传频率限制的统计窗口
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+/// 上传流量限制的统计窗口
+const BANDWIDTH_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// 已知扩展名对应的文件头，未列出的扩展名只检查扩展名
+fn signatures(ext: &str) -> &'static [&'static [u8]] {
+    match ext {
+        "png" => &[b"\x89PNG\r\n\x1a\n"],
+        // 普通 zip 与空 zip
+        "zip" => &[b"PK\x03\x04", b"PK\x05\x06"],
+        _ => &[],
+    }
+}
+
+/// 已保存的文件
+#[derive(Serialize)]
+struct StoredFile {
+    field: String,
+    /// 文件记录的 key（`{owner}/{name}`）
+    path: String,
+    /// 下载地址：S3 开启了预签名时为预签名 URL，否则为 `/api/files/{owner}/{name}`
+    url: String,
+    sha256: String,
+    size: u64,
+    /// 内容实际的存储类别，相同内容已经保存过时沿用第一次保存时的类别
+    storage_class: Option<String>,
+}
+
+/// 用户名下的文件记录，保存在 `{owner}/{name}`，指向按内容哈希保存的数据
+#[derive(Serialize, Deserialize)]
+struct FilePointer {
+    sha256: String,
+    size: u64,
+    /// 客户端提供的原始文件名，只用于展示
+    #[serde(default)]
+    filename: Option<String>,
+    /// 上传时间（Unix 时间戳，秒）
+    #[serde(default)]
+    uploaded_at: u64,
+    /// 上传者的登录提供者与 subject，早期的记录没有这两项
+    #[serde(default)]
+    provider: Option<String>,
+    #[serde(default)]
+    subject: Option<String>,
+    /// 内容实际的存储类别（由存储后端报告），`None` 表示标准类别或后端不区分存储类别
+    #[serde(default)]
+    storage_class: Option<String>,
+}
+
+/// 已保存的内容
+struct StoredBlob {
+    /// 保存之前相同内容是否已经存在
+    exists: bool,
+    /// 内容实际的存储类别，相同内容已经存在时为第一次保存时的类别
+    storage_class: Option<String>,
+}
+
+/// 用户的文件
+#[derive(Serialize)]
+pub struct UserFile {
+    pub file_id: String,
+    pub filename: Option<String>,
+    pub size: u64,
+    /// 上传时间（Unix 时间戳，秒）
+    pub uploaded_at: u64,
+    pub sha256: String,
+    pub provider: Option<String>,
+    pub subject: Option<String>,
+    pub storage_class: Option<String>,
+}
+
+/// 用户在存储中的目录名，由 subject（`{provider}:{uid}`）经 base64url 编码得到
+///
+/// 不同提供者的 uid 可能相同，直接使用 uid 会让两个用户共用同一个目录与配额
+pub fn storage_owner(subject: &str) -> String {
+    URL_SAFE_NO_PAD.encode(subject)
+}
+
+/// 上传流量的限流 key
+fn bandwidth_key(subject: &str) -> String {
+    format!("upload_bytes:{}", subject)
+}
+
+fn blob_key(sha256: &str) -> String {
+    format!("{}/{}", BLOB_PREFIX, sha256)
+}
+
+fn ref_dir(sha256: &str) -> String {
+    format!("{}/{}", REF_PREFIX, sha256)
+}
+
+/// 上传功能的运行时状态
+#[derive(Default)]
+pub struct UploadState {
+    /// 串行化“确认内容存在并登记引用”与“检查引用并删除内容”，
+    /// 避免并发上传相同内容时引用到正在被删除的内容（只在单个进程内有效）
+    blob_refs: Mutex<()>,
+}
+
+/// 保存内容并登记文件对它的引用
+///
+/// 引用在内容保存成功之后才写入，保存失败不会留下阻止内容回收的引用。
+/// 上传内容时不持有锁，只有“确认内容存在并登记引用”一步与 [`release_blob`] 互斥。
+/// 相同内容已经存在时不会重新保存，`storage_class` 不生效
+async fn store_blob(state: &AppState, sha256: &str, file_id: &str, path: &std::path::Path, storage_class: Option<&str>) -> anyhow::Result<StoredBlob> {
+    let blob_key = blob_key(sha256);
+    let exists = state.storage.exists(&blob_key).await?;
+    if !exists {
+        state.storage.put_file(BLOB_PREFIX, sha256, path, storage_class).await?;
+    }
+    let _guard = state.uploads.blob_refs.lock().await;
+    // 加锁之前内容可能因为最后一个引用被删除而被回收，需要重新保存
+    if !state.storage.exists(&blob_key).await? {
+        state.storage.put_file(BLOB_PREFIX, sha256, path, storage_class).await?;
+    }
+    let storage_class = state.storage.storage_class(&blob_key).await?;
+    state.storage.put(&ref_dir(sha256), file_id, Bytes::new()).await?;
+    Ok(StoredBlob { exists, storage_class })
+}
+
+/// 移除文件对内容的引用，没有其它引用时删除内容
+async fn release_blob(state: &AppState, sha256: &str, file_id: &str) -> anyhow::Result<()> {
+    let ref_dir = ref_dir(sha256);
+    state.storage.delete(&format!("{}/{}", ref_dir, file_id)).await?;
+    let _guard = state.uploads.blob_refs.lock().await;
+    if state.storage.list(&ref_dir).await?.is_empty() {
+        state.storage.delete(&blob_key(sha256)).await?;
+        info!(target: "audit", "已删除不再被引用的内容: sha256={}", sha256);
+    }
+    Ok(())
+}
+
+/// 按 `storage_class` 保存内容，并写入文件记录与所有者索引，返回记录的完整 key 与保存的内容
+///
+/// 记录中的存储类别为内容实际的类别。写入记录失败时撤销已经写入的部分，不会留下阻止内容回收的引用
+async fn save_file(
+    state: &AppState,
+    owner: &str,
+    file_id: &str,
+    mut pointer: FilePointer,
+    path: &std::path::Path,
+    storage_class: Option<&str>,
+) -> anyhow::Result<(String, StoredBlob)> {
+    let blob = store_blob(state, &pointer.sha256, file_id, path, storage_class).await?;
+    pointer.storage_class = blob.storage_class.clone();
+    let saved = async {
+        let record = serde_json::to_vec(&pointer)?;
+        let key = state.storage.put(owner, file_id, Bytes::from(record)).await?;
+        state.storage.put(OWNER_PREFIX, file_id, Bytes::from(owner.to_string())).await?;
+        anyhow::Ok(key)
+    }.await;
+    match saved {
+        Ok(key) => Ok((key, blob)),
+        Err(e) => {
+            discard_file(state, owner, file_id, &pointer.sha256).await;
+            Err(e)
+        }
+    }
+}
+
+/// 尽力删除写了一半的文件，失败时只记录日志
+async fn discard_file(state: &AppState, owner: &str, file_id: &str, sha256: &str) {
+    for key in [format!("{}/{}", owner, file_id), format!("{}/{}", OWNER_PREFIX, file_id)] {
+        if let Err(e) = state.storage.delete(&key).await {
+            warn!("清理 {} 失败: {:?}", key, e);
+        }
+    }
+    if let Err(e) = release_blob(state, sha256, file_id).await {
+        warn!("清理内容 {} 失败: {:?}", sha256, e);
+    }
+}
+
+/// 读取文件记录，不存在时返回 `None`
+async fn read_pointer(state: &AppState, key: &str) -> anyhow::Result<Option<FilePointer>> {
+    match state.storage.get(key).await? {
+        Some(data) => Ok(Some(serde_json::from_slice(&data)?)),
+        None => Ok(None),
+    }
+}
+
+/// 列出用户目录下的文件（大小按原始内容计算，与是否去重无关）
+pub async fn user_files(state: &AppState, owner: &str) -> anyhow::Result<Vec<UserFile>> {
+    let objects = state.storage.list(owner).await?;
+    let files: Vec<Option<UserFile>> = stream::iter(objects)
+        .map(|object| async move {
+            let pointer = read_pointer(state, &object.key).await?;
+            let file_id = object.key.rsplit('/').next().unwrap_or_default().to_string();
+            anyhow::Ok(pointer.map(|pointer| UserFile {
+                file_id,
+                filename: pointer.filename,
+                size: pointer.size,
+                uploaded_at: pointer.uploaded_at,
+                sha256: pointer.sha256,
+                provider: pointer.provider,
+                subject: pointer.subject,
+                storage_class: pointer.storage_class,
+            }))
+        })
+        .buffer_unordered(POINTER_READ_CONCURRENCY)
+        .try_collect()
+        .await?;
+    // 列出之后才被删除的记录直接跳过
+    Ok(files.into_iter().flatten().collect())
+}
+
+/// 文件列表的分页参数
+#[derive(Deserialize)]
+pub struct FileListQuery {
+    page: Option<u32>,
+    limit: Option<u32>,
+}
+
+/// 用户的存储配额
+struct Quota {
+    /// 已使用的字节数（包括本次请求中已保存的文件）
+    used: u64,
+    limit: u64,
+}
+
+impl Quota {
+    fn remaining(&self) -> u64 {
+        self.limit.saturating_sub(self.used)
+    }
+
+    fn exceeded(&self) -> ApiError {
+        ApiError::new(StatusCode::PAYLOAD_TOO_LARGE, "Upload quota exceeded")
+            .with_code("quota_exceeded")
+            .with_field("used", self.used)
+            .with_field("limit", self.limit)
+    }
+}
+
+/// 用户在 `max_bytes_per_hour` 窗口内剩余的上传流量
+struct Bandwidth<'a> {
+    limiter: &'a RateLimiter,
+    key: String,
+    remaining: u64,
+    limit: u64,
+}
+
+impl<'a> Bandwidth<'a> {
+    async fn new(limiter: &'a RateLimiter, subject: &str, limit: u64) -> Self {
+        let key = bandwidth_key(subject);
+        Self {
+            remaining: limiter.remaining(&key, limit, BANDWIDTH_WINDOW).await,
+            limiter,
+            key,
+            limit,
+        }
+    }
+
+    /// 本次还需要上传 `amount` 字节时的错误，`Retry-After` 为额度足够前需要等待的时间
+    async fn exceeded(&self, amount: u64) -> ApiError {
+        let after = self.limiter.retry_after(&self.key, amount, self.limit, BANDWIDTH_WINDOW).await;
+        ApiError::new(StatusCode::TOO_MANY_REQUESTS, "Upload bandwidth limit exceeded, please try again later")
+            .with_code("bandwidth_exceeded")
+            .with_field("limit", self.limit)
+            .with_retry_after(after)
+    }
+}
+
+/// 处理文件上传
+///
+/// 每个字段逐块写入暂存文件并同时计算 SHA-256，不在内存中缓存整个文件。
+/// 文件内容按 SHA-256 保存在 `.blobs/{sha256}`，相同内容只保存一份；
+/// 用户名下的 `{owner}/{uuid}.{ext}` 只是指向内容的记录（`owner` 见 [`storage_owner`]）。
+/// 文件名由服务端生成，原始文件名只取扩展名，避免路径穿越。
+/// 设置了 `max_user_quota_bytes` 时在读取过程中检查配额，超出时立即中止并返回 413；
+/// 设置了 `upload.max_bytes_per_hour` 时同样检查最近一小时的上传流量，超出时返回 429；
+/// 设置了 `allowed_extensions` 时拒绝其它扩展名，并检查 PNG/ZIP 的文件头，不符时返回 415。
+/// 任何一个字段失败时，本次请求中已经保存的文件都会被删除
+pub async fn upload_file(
+    State(state): State<Arc<AppState>>,
+    user: UnifiedUserInfo,
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, ApiError> {
+    // 按用户（而不是 IP）限制上传频率
+    if let Some(limit) = state.config().upload.max_requests_per_minute
+        && let Err(after) = state.rate_limiter
+            .try_record(&format!("upload:{}", user.subject), 1, limit.into(), RATE_LIMIT_WINDOW).await
+    {
+        return Err(ApiError::new(StatusCode::TOO_MANY_REQUESTS, "Too many uploads, please try again later")
+            .with_code("upload_rate_limited")
+            .with_retry_after(after));
+    }
+
+    let storage_class = requested_storage_class(&state, &headers)?;
+    let owner = storage_owner(&user.subject);
+    let mut quota = match state.config().server.max_user_quota_bytes {
+        Some(limit) => {
+            let used = user_files(&state, &owner).await
+                .map_err(|e| storage_error("list", e))?
+                .iter()
+                .map(|file| file.size)
+                .sum();
+            Some(Quota { used, limit })
+        }
+        None => None,
+    };
+    if let Some(quota) = &quota
+        && quota.remaining() == 0
+    {
+        return Err(quota.exceeded());
+    }
+
+    let mut stored = Vec::new();
+    // 本次请求已保存的文件（文件 ID 与内容哈希），失败时据此清理
+    let mut saved = Vec::new();
+    let received = async {
+        // 请求体格式错误时返回 400，超过 `max_upload_bytes` 时返回 413
+        while let Some(field) = multipart.next_field().await
+            .map_err(malformed_multipart)?
+        {
+            let name = field.name().unwrap_or("unknown").to_string();
+            // 拒绝 `upload.allowed_field_names` 之外的字段，防止客户端夹带额外文件
+            if !state.config().upload.allowed_field_names.contains(&name) {
+                return Err(ApiError::bad_request(format!("Unexpected field: {}", name)));
+            }
+
+            let original_name = field.file_name()
+                .map(|file_name| file_name.chars().take(MAX_FILENAME_LEN).collect::<String>());
+            let ext = original_name.as_deref().and_then(safe_extension);
+            let allowed = &state.config().server.allowed_extensions;
+            let ext_allowed = allowed.is_empty()
+                || ext.as_ref().is_some_and(|ext| allowed.iter().any(|allowed| allowed.eq_ignore_ascii_case(ext)));
+            if !ext_allowed {
+                return Err(unsupported_type(&name));
+            }
+            // 只在启用了扩展名限制时检查文件头
+            let signatures = match &ext {
+                Some(ext) if !allowed.is_empty() => signatures(ext),
+                _ => &[],
+            };
+
+            let file_name = match &ext {
+                Some(ext) => format!("{}.{}", Uuid::new_v4().simple(), ext),
+                None => Uuid::new_v4().simple().to_string(),
+            };
+            let (content_type, content_disposition) = download_headers(&file_name);
+            // 每个文件读取前重新计算剩余流量，本次请求中已保存的文件也计算在内。
+            // 并发的请求可能同时通过检查，超出的量最多为这些请求的大小
+            let bandwidth = match state.config().upload.max_bytes_per_hour {
+                Some(limit) => Some(Bandwidth::new(&state.rate_limiter, &user.subject, limit).await),
+                None => None,
+            };
+            if let Some(bandwidth) = &bandwidth
+                && bandwidth.remaining == 0
+            {
+                return Err(bandwidth.exceeded(1).await);
+            }
+            let temp_dir = state.config().server.upload_dir.join(TEMP_PREFIX);
+            let received = receive_field(field, &name, &temp_dir, quota.as_ref(), bandwidth.as_ref(), signatures).await?;
+            let (size, sha256) = (received.size, received.sha256.clone());
+            // 流量按实际上传的字节计算，去重不会减少
+            state.rate_limiter.record(&bandwidth_key(&user.subject), size, BANDWIDTH_WINDOW).await;
+
+            let pointer = FilePointer {
+                sha256: sha256.clone(),
+                size,
+                filename: original_name,
+                uploaded_at: SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs(),
+                provider: Some(user.provider.clone()),
+                subject: Some(user.subject.clone()),
+                storage_class: None,
+            };
+            // 相同内容已经保存过时只写入记录。写入在独立任务中进行，
+            // 客户端中途断开时不会在登记引用之后、写入记录之前被取消
+            let (key, blob) = {
+                let state = state.clone();
+                let owner = owner.clone();
+                let storage_class = storage_class.clone();
+                let file_name = file_name.clone();
+                tokio::spawn(async move { save_file(&state, &owner, &file_name, pointer, &received.file.path, storage_class.as_deref()).await })
+                    .await
+                    .map_err(|e| storage_error("store", e.into()))?
+                    .map_err(|e| storage_error("store", e))?
+            };
+            saved.push((file_name, sha256.clone()));
+            if let Some(quota) = &mut quota {
+                quota.used += size;
+            }
+
+            let url = match state.storage.download_url(&blob_key(&sha256), &content_type, &content_disposition).await {
+                Ok(Some(url)) => url,
+                Ok(None) => format!("/api/files/{}", key),
+                Err(e) => return Err(storage_error("sign", e)),
+            };
+
+            info!(target: "audit", "用户上传文件: subject={}, field={}, key={}, sha256={}, size={}, deduplicated={}, storage_class={:?}", user.subject, name, key, sha256, size, blob.exists, blob.storage_class);
+            stored.push(StoredFile {
+                field: name,
+                path: key,
+                url,
+                sha256,
+                size,
+                storage_class: blob.storage_class,
+            });
+        }
+        Ok::<_, ApiError>(())
+    }.await;
+    if let Err(e) = received {
+        // 中途失败时删除本次请求已保存的文件，它们没有返回给客户端，不应继续占用配额
+        for (file_id, sha256) in &saved {
+            discard_file(&state, &owner, file_id, sha256).await;
+        }
+        return Err(e);
+    }
+
+    Ok(Json(serde_json::json!({ "files": stored })))
+}
+
+/// 读取 multipart 请求体失败时的错误
+///
+/// 格式错误时为 400 `malformed_multipart`，超过 `max_upload_bytes` 时为 413，不会让 handler panic
+pub fn malformed_multipart(e: MultipartError) -> ApiError {
+    let status = e.status();
+    let error = ApiError::new(status, format!("Malformed multipart body: {}", e.body_text()));
+    if status == StatusCode::BAD_REQUEST {
+        error.with_code("malformed_multipart")
+    } else {
+        error
+    }
+}
+
+/// 读取 `X-Storage-Class` 请求头，不在存储后端允许的类别中时返回 400
+fn requested_storage_class(state: &AppState, headers: &HeaderMap) -> Result<Option<String>, ApiError> {
+    let Some(value) = headers.get(STORAGE_CLASS_HEADER) else {
+        return Ok(None);
+    };
+    let class = value.to_str().unwrap_or_default();
+    if !state.storage.storage_classes().iter().any(|allowed| allowed == class) {
+        return Err(ApiError::bad_request(format!("Unsupported storage class: {}", class))
+            .with_code("unsupported_storage_class"));
+    }
+    Ok(Some(class.to_string()))
+}
+
+fn unsupported_type(name: &str) -> ApiError {
+    ApiError::new(StatusCode::UNSUPPORTED_MEDIA_TYPE, format!("File type of field {} is not allowed", name))
+        .with_code("unsupported_file_type")
+}
+
+/// 暂存中的上传文件，离开作用域时删除
+struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    async fn create(dir: &std::path::Path) -> std::io::Result<(Self, fs::File)> {
+        fs::create_dir_all(dir).await?;
+        let path = dir.join(Uuid::new_v4().simple().to_string());
+        let file = fs::File::create(&path).await?;
+        Ok((Self { path }, file))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// 已接收的字段内容
+struct ReceivedField {
+    file: TempFile,
+    size: u64,
+    sha256: String,
+}
+
+/// 逐块把字段内容写入 `temp_dir` 下的暂存文件并计算 SHA-256，超出配额、上传流量或文件头不符时立即中止
+///
+/// `signatures` 不为空时，文件开头必须与其中之一匹配。
+/// 读取的总量受 `max_upload_bytes` 限制
+async fn receive_field(
+    mut field: Field<'_>,
+    name: &str,
+    temp_dir: &std::path::Path,
+    quota: Option<&Quota>,
+    bandwidth: Option<&Bandwidth<'_>>,
+    signatures: &[&[u8]],
+) -> Result<ReceivedField, ApiError> {
+    let check_header = |header: &[u8]| {
+        if signatures.is_empty() || signatures.iter().any(|signature| header.starts_with(signature)) {
+            Ok(())
+        } else {
+            Err(unsupported_type(name))
+        }
+    };
+    let write_error = |e: std::io::Error| storage_error("store", e.into());
+    let (file, writer) = TempFile::create(temp_dir).await.map_err(write_error)?;
+    let mut writer = BufWriter::new(writer);
+    let mut hasher = Sha256::new();
+    let mut header = Vec::with_capacity(SNIFF_LEN);
+    let mut size = 0u64;
+    while let Some(chunk) = field.chunk().await
+        .map_err(|e| malformed_multipart(e).with_field("field", name))?
+    {
+        let total = size + chunk.len() as u64;
+        if let Some(quota) = quota
+            && total > quota.remaining()
+        {
+            return Err(quota.exceeded());
+        }
+        if let Some(bandwidth) = bandwidth
+            && total > bandwidth.remaining
+        {
+            return Err(bandwidth.exceeded(total).await);
+        }
+        if header.len() < SNIFF_LEN {
+            let take = chunk.len().min(SNIFF_LEN - header.len());
+            header.extend_from_slice(&chunk[..take]);
+            if header.len() == SNIFF_LEN {
+                check_header(&header)?;
+            }
+        }
+        hasher.update(&chunk);
+        writer.write_all(&chunk).await.map_err(write_error)?;
+        size = total;
+    }
+    // 文件比 SNIFF_LEN 短时在结束后检查
+    if header.len() < SNIFF_LEN {
+        check_header(&header)?;
+    }
+    writer.flush().await.map_err(write_error)?;
+    Ok(ReceivedField {
+        file,
+        size,
+        sha256: format!("{:x}", hasher.finalize()),
+    })
+}
+
+/// 列出当前用户上传的文件
+///
+/// 按上传时间从新到旧排序，`page` 从 1 开始，`limit` 最大为 100
+pub async fn list_files(
+    State(state): State<Arc<AppState>>,
+    user: UnifiedUserInfo,
+    Query(query): Query<FileListQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let page = query.page.unwrap_or(1).max(1);
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT);
+
+    let mut files = user_files(&state, &storage_owner(&user.subject)).await
+        .map_err(|e| storage_error("list", e))?;
+    files.sort_by(|a, b| b.uploaded_at.cmp(&a.uploaded_at).then_with(|| a.file_id.cmp(&b.file_id)));
+    let total = files.len();
+    let files: Vec<_> = files.into_iter()
+        .skip((page as usize - 1).saturating_mul(limit as usize))
+        .take(limit as usize)
+        .collect();
+
+    Ok(Json(serde_json::json!({
+        "files": files,
+        "total": total,
+        "page": page,
+        "limit": limit,
+    })))
+}
+
+/// 下载文件（公开访问）
+///
+/// 文件必须位于路径中 `owner` 目录下；不存在与格式错误都返回 404，不泄露文件是否存在。
+/// 图片以 inline 返回，其它类型（包括 SVG）一律作为附件下载，避免在本站域名下执行上传的内容
+pub async fn download_file(
+    State(state): State<Arc<AppState>>,
+    Path((owner, file_id)): Path<(String, String)>,
+) -> Result<impl IntoResponse, ApiError> {
+    let not_found = || ApiError::not_found("File not found");
+    let key = file_key(&owner, &file_id).ok_or_else(not_found)?;
+    let pointer = read_pointer(&state, &key).await
+        .map_err(|e| storage_error("read", e))?
+        .ok_or_else(not_found)?;
+    // 逐块读取内容，并发下载大文件时不会把整个文件读入内存
+    let body = state.storage.get_stream(&blob_key(&pointer.sha256)).await
+        .map_err(|e| storage_error("read", e))?
+        .ok_or_else(|| {
+            warn!("文件记录 {} 指向的内容 {} 不存在", key, pointer.sha256);
+            not_found()
+        })?;
+
+    let (content_type, content_disposition) = download_headers(&file_id);
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::CONTENT_DISPOSITION, content_disposition),
+            (header::CONTENT_LENGTH, pointer.size.to_string()),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+        ],
+        body,
+    ))
+}
+
+/// 下载文件时的 `Content-Type` 与 `Content-Disposition`，预签名地址也使用相同的值
+///
+/// 图片以 inline 返回，其它类型（包括 SVG）一律作为附件下载
+fn download_headers(file_id: &str) -> (String, String) {
+    let mime = mime_guess::from_path(file_id).first_or_octet_stream();
+    let disposition = if mime.type_() == mime_guess::mime::IMAGE && mime.subtype() != mime_guess::mime::SVG {
+        "inline"
+    } else {
+        "attachment"
+    };
+    (mime.to_string(), format!("{}; filename=\"{}\"", disposition, file_id))
+}
+
+/// 删除当前用户上传的文件
+///
+/// 删除是幂等的：文件已被删除（或从未存在）时同样返回 204，属于其他用户时返回 403，
+/// 只有格式错误的文件 ID 返回 404。
+/// 删除用户名下的记录后，内容没有其它引用时一并删除（预签名地址随之失效）
+pub async fn delete_file(
+    State(state): State<Arc<AppState>>,
+    user: UnifiedUserInfo,
+    Path(file_id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let not_found = || ApiError::not_found("File not found");
+    let owner = storage_owner(&user.subject);
+    let key = file_key(&owner, &file_id).ok_or_else(not_found)?;
+    let owner_key = format!("{}/{}", OWNER_PREFIX, file_id);
+
+    let indexed_owner = state.storage.get(&owner_key).await
+        .map_err(|e| storage_error("read", e))?;
+    if indexed_owner.is_some_and(|indexed_owner| indexed_owner != owner.as_bytes()) {
+        return Err(ApiError::forbidden("Access denied"));
+    }
+    // 没有索引时只认当前用户名下的记录
+    let pointer = read_pointer(&state, &key).await
+        .map_err(|e| storage_error("read", e))?;
+
+    // 存储后端删除不存在的对象视为成功，重复删除不会出错
+    state.storage.delete(&key).await
+        .map_err(|e| storage_error("delete", e))?;
+    state.storage.delete(&owner_key).await
+        .map_err(|e| storage_error("delete", e))?;
+    let Some(pointer) = pointer else {
+        return Ok(StatusCode::NO_CONTENT);
+    };
+    // 记录已经删除，清理内容失败只会留下无人引用的数据，不影响本次请求
+    if let Err(e) = release_blob(&state, &pointer.sha256, &file_id).await {
+        warn!("清理内容 {} 失败: {:?}", pointer.sha256, e);
+    }
+    info!(target: "audit", "用户删除文件: subject={}, key={}", user.subject, key);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// 删除用户目录下的全部文件（删除账号时使用），返回删除的文件数
+///
+/// 与 [`delete_file`] 一样删除记录、所有者索引与引用，没有其它引用的内容一并删除
+pub async fn delete_user_files(state: &AppState, owner: &str) -> anyhow::Result<usize> {
+    let files = user_files(state, owner).await?;
+    for file in &files {
+        state.storage.delete(&format!("{}/{}", owner, file.file_id)).await?;
+        state.storage.delete(&format!("{}/{}", OWNER_PREFIX, file.file_id)).await?;
+        release_blob(state, &file.sha256, &file.file_id).await?;
+    }
+    Ok(files.len())
+}
+
+/// 拼接对象 key，只接受服务端生成的文件名格式（`{stem}.{ext}` 或不带扩展名）
+fn file_key(owner: &str, name: &str) -> Option<String> {
+    let (stem, ext) = name.split_once('.').unwrap_or((name, ""));
+    let valid = is_safe_path_segment(owner)
+        && is_safe_path_segment(stem)
+        && ext.bytes().all(|b| b.is_ascii_alphanumeric());
+    valid.then(|| format!("{}/{}", owner, name))
+}
+
+fn storage_error(action: &str, e: anyhow::Error) -> ApiError {
+    tracing::error!("存储后端操作失败 ({}): {:?}", action, e);
+    ApiError::internal(format!("Failed to {} file", action))
+}
+
+/// 取原始文件名的扩展名（转为小写），只接受较短的字母数字扩展名
+fn safe_extension(file_name: &str) -> Option<String> {
+    let ext = std::path::Path::new(file_name).extension()?.to_str()?.to_ascii_lowercase();
+    let valid = !ext.is_empty()
+        && ext.len() <= MAX_EXTENSION_LEN
+        && ext.bytes().all(|b| b.is_ascii_alphanumeric());
+    valid.then_some(ext)
+}
+
+/// 能否直接作为目录名使用
+fn is_safe_path_segment(segment: &str) -> bool {
+    !segment.is_empty()
+        && segment.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, extract::FromRequest, http::Request, response::Response};
+
+    async fn first_field_error(body: &'static str) -> Response {
+        let request = Request::builder()
+            .method("POST")
+            .header(header::CONTENT_TYPE, "multipart/form-data; boundary=BOUNDARY")
+            .body(Body::from(body))
+            .unwrap();
+        let mut multipart = Multipart::from_request(request, &()).await.unwrap();
+        let error = multipart.next_field().await.map_err(malformed_multipart)
+            .expect_err("malformed body should fail");
+        error.into_response()
+    }
+
+    async fn error_code(response: Response) -> serde_json::Value {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice::<serde_json::Value>(&body).unwrap()["code"].clone()
+    }
+
+    #[tokio::test]
+    async fn received_field_is_hashed_and_removed_on_drop() {
+        let body = "--BOUNDARY\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\r\nhello\r\n--BOUNDARY--\r\n";
+        let request = Request::builder()
+            .method("POST")
+            .header(header::CONTENT_TYPE, "multipart/form-data; boundary=BOUNDARY")
+            .body(Body::from(body))
+            .unwrap();
+        let mut multipart = Multipart::from_request(request, &()).await.unwrap();
+        let field = multipart.next_field().await.unwrap().unwrap();
+        let temp_dir = std::env::temp_dir().join(format!("ysm_upload-test-{}", Uuid::new_v4().simple()));
+
+        let received = receive_field(field, "file", &temp_dir, None, None, &[]).await.ok().unwrap();
+        assert_eq!(received.size, 5);
+        assert_eq!(received.sha256, format!("{:x}", Sha256::digest(b"hello")));
+        assert_eq!(std::fs::read(&received.file.path).unwrap(), b"hello");
+
+        let path = received.file.path.clone();
+        drop(received);
+        assert!(!path.exists());
+        std::fs::remove_dir(&temp_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn truncated_multipart_body_is_bad_request() {
+        let response = first_field_error("--BOUNDARY\r\nContent-Disposition: form-data; name=\"skin\"").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(error_code(response).await, "malformed_multipart");
+    }
+
+    #[tokio::test]
+    async fn body_without_boundary_is_bad_request() {
+        let response = first_field_error("this is not a multipart body").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(error_code(response).await, "malformed_multipart");
+    }
+}